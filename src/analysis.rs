@@ -0,0 +1,254 @@
+//! Parameter quality checks, see [`LCG::analyze`]
+
+use crate::{math, modulo, LCG};
+use num::{Integer, One, Zero};
+use num_bigint::BigInt;
+use std::fmt;
+
+/// Periods at or below this are considered short enough to walk exhaustively
+const WALKABLE_PERIOD_BITS: u64 = 32;
+
+/// Prime power factors of m below this are reported as leaking a short residue
+const SMALL_FACTOR_BOUND: u32 = 1 << 16;
+
+/// A structural property of an LCG's parameters which changes how it should be attacked
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Finding {
+    /// The Hull-Dobell conditions hold so every value in [0, m) is part of one cycle
+    FullPeriod,
+    /// gcd(c, m) != 1, breaking the first Hull-Dobell condition
+    IncrementSharesFactor {
+        /// gcd(c, m)
+        gcd: BigInt,
+    },
+    /// A prime dividing m doesn't divide a-1, breaking the second Hull-Dobell condition
+    MultiplierMissesPrime {
+        /// The prime factor of m which a-1 is missing
+        prime: BigInt,
+    },
+    /// 4 divides m but not a-1, breaking the third Hull-Dobell condition
+    MultiplierNotOneModFour,
+    /// gcd(a, m) != 1 so a has no inverse mod m
+    NonInvertibleMultiplier {
+        /// gcd(a, m)
+        gcd: BigInt,
+    },
+    /// c ≡ 0 so the generator is purely multiplicative and 0 maps to itself
+    ZeroFixedPoint,
+    /// a ≡ 0 or a ≡ 1, so the output is constant or an arithmetic progression
+    DegenerateMultiplier,
+    /// a or m-a is at most sqrt(m)
+    WeakMultiplier,
+    /// 2^bits divides m so the low bits of the state form their own short LCG
+    ShortLowBitPeriods {
+        /// Largest k such that 2^k divides m
+        bits: u32,
+    },
+    /// An odd prime power factor of m is small enough that the state reduced by it cycles quickly
+    SmallFactor {
+        /// The prime power dividing m
+        factor: BigInt,
+    },
+    /// The current state is not on its cycle yet, see [`LCG::tail_length`]
+    Tail {
+        /// Steps before the state enters its cycle
+        length: BigInt,
+    },
+    /// The cycle from the current state is short enough to walk exhaustively
+    ShortPeriod {
+        /// Length of the cycle, see [`LCG::period`]
+        period: BigInt,
+    },
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::FullPeriod => write!(f, "full period: every value mod m is visited so brute forcing the cycle costs as much as brute forcing m"),
+            Finding::IncrementSharesFactor { gcd } => write!(f, "gcd(c, m) = {}: not full period, the generator splits into several shorter cycles", gcd),
+            Finding::MultiplierMissesPrime { prime } => write!(f, "a-1 is not divisible by {} which divides m: not full period, the state mod {} cycles early", prime, prime),
+            Finding::MultiplierNotOneModFour => write!(f, "4 divides m but not a-1: not full period, the low two bits cycle early"),
+            Finding::NonInvertibleMultiplier { gcd } => write!(f, "gcd(a, m) = {}: prev() and negative jumps fail and every state has either zero or {} predecessors", gcd, gcd),
+            Finding::ZeroFixedPoint => write!(f, "c = 0: the generator is purely multiplicative, 0 maps to itself and outputs are a^n * seed so the index of an output is a discrete log"),
+            Finding::DegenerateMultiplier => write!(f, "a is 0 or 1: outputs are constant or an arithmetic progression and need no real cracking"),
+            Finding::WeakMultiplier => write!(f, "a or m-a is below sqrt(m): consecutive outputs lie on a handful of lines so they are easy to tell apart from random and lattice attacks need very few samples"),
+            Finding::ShortLowBitPeriods { bits } => write!(f, "2^{} divides m: bit i of the state has period at most 2^(i+1) so leaked low bits are predictable on their own and high bits can be lifted one at a time", bits),
+            Finding::SmallFactor { factor } => write!(f, "{} divides m: state mod {} is itself an LCG which cycles within {} steps and can be attacked separately", factor, factor, factor),
+            Finding::Tail { length } => write!(f, "the state leaves its cycle behind after {} steps: those states can never be revisited so rewinding into them is ambiguous", length),
+            Finding::ShortPeriod { period } => write!(f, "period is {}: short enough to walk the whole cycle", period),
+        }
+    }
+}
+
+/// Summary of the parameter checks done by [`LCG::analyze`]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LcgReport {
+    /// Whether the Hull-Dobell conditions hold
+    pub full_period: bool,
+    /// Whether a is invertible mod m, aka whether [`LCG::prev`] works
+    pub invertible: bool,
+    /// Cycle length from the current state
+    pub period: BigInt,
+    /// Steps before the current state enters its cycle
+    pub tail_length: BigInt,
+    /// Every structural property worth knowing before choosing an attack
+    pub findings: Vec<Finding>,
+}
+
+impl fmt::Display for LcgReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for finding in &self.findings {
+            writeln!(f, "- {}", finding)?;
+        }
+        Ok(())
+    }
+}
+
+impl LCG {
+    /// Check the parameters for structural weaknesses and explain what each one means for an attack
+    ///
+    /// Covers the Hull-Dobell full period conditions, invertibility of a, fixed points, short low bit periods and the cycle from the current state.
+    /// Relies on factoring m
+    pub fn analyze(&self) -> LcgReport {
+        let m = &self.m;
+        let (a, c) = (modulo(&self.a, m), modulo(&self.c, m));
+        let a_minus_one: BigInt = &a - 1;
+        let factors = math::factor(m);
+        let mut findings = vec![];
+
+        let c_gcd = c.gcd(m);
+        if !c_gcd.is_one() {
+            findings.push(Finding::IncrementSharesFactor { gcd: c_gcd });
+        }
+        for (p, _) in &factors {
+            if !(&a_minus_one % p).is_zero() {
+                findings.push(Finding::MultiplierMissesPrime { prime: p.clone() });
+            }
+        }
+        if (m % 4u32).is_zero() && !(&a_minus_one % 4u32).is_zero() {
+            findings.push(Finding::MultiplierNotOneModFour);
+        }
+        let full_period = findings.is_empty();
+        if full_period {
+            findings.push(Finding::FullPeriod);
+        }
+
+        let a_gcd = a.gcd(m);
+        let invertible = a_gcd.is_one();
+        if !invertible {
+            findings.push(Finding::NonInvertibleMultiplier { gcd: a_gcd });
+        }
+        if c.is_zero() {
+            findings.push(Finding::ZeroFixedPoint);
+        }
+        if a.is_zero() || a.is_one() {
+            findings.push(Finding::DegenerateMultiplier);
+        } else {
+            let smallest = std::cmp::min(a.clone(), m - &a);
+            if &smallest * &smallest <= *m {
+                findings.push(Finding::WeakMultiplier);
+            }
+        }
+
+        for (p, e) in &factors {
+            if *p == BigInt::from(2) {
+                findings.push(Finding::ShortLowBitPeriods { bits: *e });
+            } else if p.pow(*e) < BigInt::from(SMALL_FACTOR_BOUND) && p.pow(*e) != *m {
+                findings.push(Finding::SmallFactor { factor: p.pow(*e) });
+            }
+        }
+
+        let (tail_length, period) = (self.tail_length(), self.period());
+        if !tail_length.is_zero() {
+            findings.push(Finding::Tail {
+                length: tail_length.clone(),
+            });
+        }
+        if period.bits() <= WALKABLE_PERIOD_BITS {
+            findings.push(Finding::ShortPeriod {
+                period: period.clone(),
+            });
+        }
+
+        LcgReport {
+            full_period,
+            invertible,
+            period,
+            tail_length,
+            findings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Finding, LCG};
+    use num_bigint::ToBigInt;
+
+    #[test]
+    fn it_reports_full_period_power_of_two_lcg() {
+        let java = LCG {
+            state: 0.to_bigint().unwrap(),
+            a: 0x5DEECE66Du64.to_bigint().unwrap(),
+            c: 11.to_bigint().unwrap(),
+            m: (1u64 << 48).to_bigint().unwrap(),
+        };
+        let report = java.analyze();
+        assert!(report.full_period);
+        assert!(report.invertible);
+        assert_eq!(report.period, java.m);
+        assert_eq!(
+            report.findings,
+            vec![
+                Finding::FullPeriod,
+                Finding::ShortLowBitPeriods { bits: 48 }
+            ]
+        );
+    }
+
+    #[test]
+    fn it_reports_broken_hull_dobell_conditions() {
+        let rand = LCG {
+            state: 3.to_bigint().unwrap(),
+            a: 6.to_bigint().unwrap(),
+            c: 0.to_bigint().unwrap(),
+            m: 360.to_bigint().unwrap(),
+        };
+        let report = rand.analyze();
+        assert!(!report.full_period);
+        assert!(!report.invertible);
+        assert_eq!(
+            report.findings,
+            vec![
+                Finding::IncrementSharesFactor {
+                    gcd: 360.to_bigint().unwrap()
+                },
+                Finding::MultiplierMissesPrime {
+                    prime: 2.to_bigint().unwrap()
+                },
+                Finding::MultiplierMissesPrime {
+                    prime: 3.to_bigint().unwrap()
+                },
+                Finding::MultiplierNotOneModFour,
+                Finding::NonInvertibleMultiplier {
+                    gcd: 6.to_bigint().unwrap()
+                },
+                Finding::ZeroFixedPoint,
+                Finding::WeakMultiplier,
+                Finding::ShortLowBitPeriods { bits: 3 },
+                Finding::SmallFactor {
+                    factor: 9.to_bigint().unwrap()
+                },
+                Finding::SmallFactor {
+                    factor: 5.to_bigint().unwrap()
+                },
+                Finding::Tail {
+                    length: 3.to_bigint().unwrap()
+                },
+                Finding::ShortPeriod {
+                    period: 1.to_bigint().unwrap()
+                },
+            ]
+        );
+    }
+}
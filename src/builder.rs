@@ -0,0 +1,218 @@
+//! Validated construction of [`LCG`]s from loosely typed input
+
+use crate::{modulo, LcgError, LCG};
+use num::{Num, Signed, Zero};
+use num_bigint::BigInt;
+
+/// Anything [`LCG::new`] and [`LCGBuilder`] accept as a parameter
+///
+/// Implemented for every primitive integer, [`BigInt`], and strings in decimal or `0x` prefixed hex
+pub trait Param {
+    /// Convert into a BigInt, failing if a string doesn't parse
+    fn into_param(self) -> Result<BigInt, LcgError>;
+}
+
+macro_rules! impl_param {
+    ($($t:ty),*) => {
+        $(
+            impl Param for $t {
+                fn into_param(self) -> Result<BigInt, LcgError> {
+                    Ok(BigInt::from(self))
+                }
+            }
+        )*
+    };
+}
+
+impl_param!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl Param for BigInt {
+    fn into_param(self) -> Result<BigInt, LcgError> {
+        Ok(self)
+    }
+}
+
+impl Param for &BigInt {
+    fn into_param(self) -> Result<BigInt, LcgError> {
+        Ok(self.clone())
+    }
+}
+
+impl Param for &str {
+    fn into_param(self) -> Result<BigInt, LcgError> {
+        let trimmed = self.trim();
+        let (negative, digits) = match trimmed.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed),
+        };
+        let (radix, body) = match digits
+            .strip_prefix("0x")
+            .or_else(|| digits.strip_prefix("0X"))
+        {
+            Some(hex) => (16, hex),
+            None => (10, digits),
+        };
+        // from_str_radix accepts its own sign, which would let "--5", "0x+5" or "-0x-5" through
+        match BigInt::from_str_radix(body, radix) {
+            Ok(value) if !body.starts_with(&['+', '-'][..]) => {
+                Ok(if negative { -value } else { value })
+            }
+            _ => Err(LcgError::InvalidNumber(self.to_string())),
+        }
+    }
+}
+
+impl Param for String {
+    fn into_param(self) -> Result<BigInt, LcgError> {
+        self.as_str().into_param()
+    }
+}
+
+impl Param for &String {
+    fn into_param(self) -> Result<BigInt, LcgError> {
+        self.as_str().into_param()
+    }
+}
+
+/// Builder for [`LCG`] which validates and normalises on [`build`](LCGBuilder::build)
+///
+/// `c` defaults to 0, everything else has to be set
+#[derive(Debug, Clone, Default)]
+pub struct LCGBuilder {
+    state: Option<Result<BigInt, LcgError>>,
+    a: Option<Result<BigInt, LcgError>>,
+    c: Option<Result<BigInt, LcgError>>,
+    m: Option<Result<BigInt, LcgError>>,
+}
+
+impl LCGBuilder {
+    /// Set the seed
+    pub fn state(mut self, state: impl Param) -> Self {
+        self.state = Some(state.into_param());
+        self
+    }
+
+    /// Set the multiplier
+    pub fn a(mut self, a: impl Param) -> Self {
+        self.a = Some(a.into_param());
+        self
+    }
+
+    /// Set the increment
+    pub fn c(mut self, c: impl Param) -> Self {
+        self.c = Some(c.into_param());
+        self
+    }
+
+    /// Set the modulus
+    pub fn m(mut self, m: impl Param) -> Self {
+        self.m = Some(m.into_param());
+        self
+    }
+
+    /// Validate the parameters, see [`LCG::new`]
+    pub fn build(self) -> Result<LCG, LcgError> {
+        let required = |value: Option<Result<BigInt, LcgError>>, name| {
+            value.unwrap_or(Err(LcgError::MissingParameter(name)))
+        };
+        LCG::new(
+            required(self.state, "state")?,
+            required(self.a, "a")?,
+            self.c.unwrap_or_else(|| Ok(BigInt::zero()))?,
+            required(self.m, "m")?,
+        )
+    }
+}
+
+impl LCG {
+    /// Create an LCG from anything implementing [`Param`], normalising every parameter into [0, m)
+    ///
+    /// Returns [`LcgError::NonPositiveModulus`] for m <= 0, which would otherwise panic with a division by zero in [`rand`](LCG::rand)
+    pub fn new(
+        state: impl Param,
+        a: impl Param,
+        c: impl Param,
+        m: impl Param,
+    ) -> Result<LCG, LcgError> {
+        let m = m.into_param()?;
+        if !m.is_positive() {
+            return Err(LcgError::NonPositiveModulus);
+        }
+        Ok(LCG {
+            state: modulo(&state.into_param()?, &m),
+            a: modulo(&a.into_param()?, &m),
+            c: modulo(&c.into_param()?, &m),
+            m,
+        })
+    }
+
+    /// Start building an LCG parameter by parameter
+    pub fn builder() -> LCGBuilder {
+        LCGBuilder::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LcgError, LCG};
+    use num_bigint::{BigInt, ToBigInt};
+
+    #[test]
+    fn it_normalises_parameters() {
+        let rand = LCG::new(-1, "-5", "0x10", 7u8).unwrap();
+        assert_eq!(
+            rand,
+            LCG {
+                state: 6.to_bigint().unwrap(),
+                a: 2.to_bigint().unwrap(),
+                c: 2.to_bigint().unwrap(),
+                m: 7.to_bigint().unwrap(),
+            }
+        );
+        let java = LCG::builder()
+            .state(" 0x1234 ")
+            .a("0x5DEECE66D")
+            .c(11)
+            .m(BigInt::from(1) << 48)
+            .build()
+            .unwrap();
+        assert_eq!(java.a, 0x5DEECE66Du64.to_bigint().unwrap());
+        assert_eq!(java.state, 0x1234.to_bigint().unwrap());
+    }
+
+    #[test]
+    fn it_rejects_invalid_parameters() {
+        assert_eq!(LCG::new(1, 2, 3, 0), Err(LcgError::NonPositiveModulus));
+        assert_eq!(LCG::new(1, 2, 3, -7), Err(LcgError::NonPositiveModulus));
+        assert_eq!(
+            LCG::new("12z", 2, 3, 7),
+            Err(LcgError::InvalidNumber("12z".to_string()))
+        );
+        assert_eq!(
+            LCG::new("--5", 2, 3, 7),
+            Err(LcgError::InvalidNumber("--5".to_string()))
+        );
+        for signed in &["0x+5", "0x-5", "-0x-5"] {
+            assert_eq!(
+                LCG::new(*signed, 2, 3, 7),
+                Err(LcgError::InvalidNumber(signed.to_string()))
+            );
+        }
+        assert_eq!(
+            LCG::new("0x", 2, 3, 7),
+            Err(LcgError::InvalidNumber("0x".to_string()))
+        );
+        assert_eq!(
+            LCG::builder().state(1).m(7).build(),
+            Err(LcgError::MissingParameter("a"))
+        );
+        assert_eq!(
+            LCG::builder().state(1).a("x").m(7).build(),
+            Err(LcgError::InvalidNumber("x".to_string()))
+        );
+        assert_eq!(
+            LCG::builder().state(1).a(3).m(7).build().unwrap().c,
+            0.into()
+        );
+    }
+}
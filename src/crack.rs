@@ -0,0 +1,565 @@
+//! Recovering LCG parameters from observed outputs
+
+use crate::{math, modulo, LcgInt, LCG};
+use itertools::izip;
+use num::{Integer, One, Signed, ToPrimitive, Zero};
+use num_bigint::BigInt;
+use std::cmp::Ordering;
+
+/// Crackers give up rather than list more multipliers than this, which only happens for degenerate input like a constant sequence
+pub(crate) const MAX_CANDIDATES: usize = 1 << 16;
+
+/// Spurious factors of the gcd below this are stripped when refining the modulus, bigger ones are left alone
+pub(crate) const REFINE_BOUND: usize = 1 << 12;
+
+/// Refinement stops enumerating after this many divisors of the gcd
+const MAX_MODULI: usize = 1 << 12;
+
+/// Parameters which are already known before cracking, see [`crack_lcg_with`]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct KnownParams {
+    /// Multiplier
+    pub a: Option<BigInt>,
+    /// Increment
+    pub c: Option<BigInt>,
+    /// Modulus
+    pub m: Option<BigInt>,
+    /// State right before the first value, such as a known seed, which counts as one more value
+    pub state: Option<BigInt>,
+}
+
+/// Modulus inferred from values, see [`estimate_modulus`]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ModulusEstimate {
+    /// Smallest modulus the values are consistent with
+    pub modulus: BigInt,
+    /// Every consistent modulus that was found, smallest first and starting with `modulus`
+    pub consistent: Vec<BigInt>,
+}
+
+impl ModulusEstimate {
+    /// Whether more than one modulus explains the values, in which case more values will tell them apart
+    pub fn is_ambiguous(&self) -> bool {
+        self.consistent.len() > 1
+    }
+}
+
+/// One LCG which fits every observation, see [`CrackResult`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    /// The cracked LCG, with the state set to the last value
+    pub lcg: LCG,
+    /// Estimated probability that this isn't the generator which produced the values
+    pub spurious: f64,
+    /// How many observed values the candidate reproduces, which is all of them
+    pub verified: usize,
+}
+
+/// Every LCG consistent with a set of observations, most likely first, see [`crack_lcg_ranked`]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CrackResult {
+    /// Candidates sorted by how likely they are to be spurious
+    pub candidates: Vec<Candidate>,
+}
+
+impl CrackResult {
+    /// The most likely candidate
+    pub fn best(&self) -> Option<&Candidate> {
+        self.candidates.first()
+    }
+
+    /// Whether exactly one LCG fits, it can still be spurious if the modulus was inferred from too few values
+    pub fn is_unique(&self) -> bool {
+        self.candidates.len() == 1
+    }
+}
+
+pub(crate) fn to_bigints<T: Clone + Into<BigInt>>(values: &[T]) -> Vec<BigInt> {
+    values.iter().cloned().map(Into::into).collect()
+}
+
+/// Every a in [0, m) with `a*x ≡ y (mod m)` for every `(x, y)` pair, or None if there are more than [`MAX_CANDIDATES`]
+fn solve_multiplier<'a>(
+    equations: impl Iterator<Item = (&'a BigInt, BigInt)>,
+    m: &BigInt,
+) -> Option<Vec<BigInt>> {
+    let (mut residue, mut step) = (BigInt::zero(), BigInt::one());
+    for (x, y) in equations {
+        let (r, s) = math::solve_linear(x, &y, m)?;
+        let (r, s) = math::crt(&residue, &step, &r, &s)?;
+        residue = r;
+        step = s;
+    }
+    let count = (m / &step).to_usize().filter(|&x| x <= MAX_CANDIDATES)?;
+    Some((0..count).map(|k| &residue + &step * k).collect())
+}
+
+/// Whether every consecutive pair of values is one step of `x -> a*x + c (mod m)`
+fn fits(values: &[BigInt], a: &BigInt, c: &BigInt, m: &BigInt) -> bool {
+    izip!(values, values.iter().skip(1)).all(|(x, y)| modulo(&(x * a + c), m) == *y)
+}
+
+/// k such that `values - k` outputs give [`infer_modulus`] that many expressions to take the gcd of
+///
+/// The first expression takes k + 1 values, such as 4 for `d2*d0 - d1^2` with nothing known, and every value after that adds one more.
+/// A known seed counts as a value so it lowers k by one
+pub(crate) fn values_per_zero(known: &KnownParams) -> usize {
+    let values = match (&known.a, &known.c) {
+        (Some(_), Some(_)) => 1,
+        (None, None) => 3,
+        _ => 2,
+    };
+    values - usize::from(known.state.is_some())
+}
+
+/// The values with the known state, if any, in front
+fn with_state<T: Clone + Into<BigInt>>(known: &KnownParams, values: &[T]) -> Vec<BigInt> {
+    known
+        .state
+        .iter()
+        .cloned()
+        .chain(to_bigints(values))
+        .collect()
+}
+
+/// gcd of expressions which are multiples of m, using whatever parameters are already known to need fewer values
+fn infer_modulus(known: &KnownParams, values: &[BigInt]) -> Option<BigInt> {
+    let diffs = izip!(values, values.iter().skip(1))
+        .map(|(a, b)| b - a)
+        .collect::<Vec<_>>();
+    let zeroes = match (&known.a, &known.c) {
+        (Some(a), Some(c)) => izip!(values, values.iter().skip(1))
+            .map(|(x, y)| y - a * x - c)
+            .collect::<Vec<_>>(),
+        (Some(a), None) => izip!(&diffs, diffs.iter().skip(1))
+            .map(|(d0, d1)| d1 - a * d0)
+            .collect(),
+        (None, Some(c)) => izip!(values, values.iter().skip(1), values.iter().skip(2))
+            .map(|(x0, x1, x2)| (x2 - c) * x0 - (x1 - c) * x1)
+            .collect(),
+        (None, None) => izip!(&diffs, diffs.iter().skip(1), diffs.iter().skip(2))
+            .map(|(a, b, c)| c * a - b * b)
+            .collect(),
+    };
+    let mut modulus = zeroes.iter().fold(BigInt::zero(), |sum, val| sum.gcd(val));
+    if known.a.is_none() && known.c.is_none() && !modulus.is_zero() {
+        // every zero is g^2 * (something ≡ 0 mod m/gcd(g, m)) for g the gcd of the differences,
+        // so the gcd overshoots m by at least g
+        modulus /= diffs.iter().fold(BigInt::zero(), |sum, val| sum.gcd(val));
+    }
+    if modulus.is_zero() {
+        None
+    } else {
+        Some(modulus)
+    }
+}
+
+/// Divisors of `multiple` bigger than `max` which only differ from it by primes below [`REFINE_BOUND`], smallest first
+///
+/// A big spurious factor would need the gcd factored so it is left alone
+pub(crate) fn candidate_moduli(multiple: &BigInt, max: &BigInt) -> Vec<BigInt> {
+    let (factors, _) = math::trial_factor(multiple, REFINE_BOUND);
+    // only divisors d with multiple/d > max are worth dividing out
+    let limit = multiple / (max + 1);
+    let mut divisors = vec![BigInt::one()];
+    for (p, e) in factors {
+        let mut next = vec![];
+        for d in &divisors {
+            let mut d = d.clone();
+            for _ in 0..=e {
+                if d > limit || divisors.len() + next.len() >= MAX_MODULI {
+                    break;
+                }
+                next.push(d.clone());
+                d *= &p;
+            }
+        }
+        divisors = next;
+    }
+    let mut moduli = divisors
+        .into_iter()
+        .map(|d| multiple / d)
+        .collect::<Vec<_>>();
+    moduli.sort();
+    moduli
+}
+
+/// Every candidate modulus that explains the values
+fn refine_modulus(known: &KnownParams, values: &[BigInt], multiple: &BigInt) -> Vec<BigInt> {
+    let max = values.iter().max().cloned().unwrap_or_default();
+    candidate_moduli(multiple, &max)
+        .into_iter()
+        .filter(|m| {
+            let known = KnownParams {
+                m: Some(m.clone()),
+                ..known.clone()
+            };
+            !crack_values(&known, values).is_empty()
+        })
+        .collect()
+}
+
+/// Infer the modulus from consecutive values without cracking a and c
+///
+/// The gcd in [`crack_lcg`] is a multiple of m which can pick up small spurious factors when there are only a few values.
+/// Those are stripped again by trying every divisor of the gcd that is bigger than all of the values.
+/// Divisors of m bigger than the values fit as well, so a short or unlucky sequence can leave several moduli, which is flagged by [`ModulusEstimate::is_ambiguous`]
+pub fn estimate_modulus<T: Clone + Into<BigInt>>(values: &[T]) -> Option<ModulusEstimate> {
+    estimate_modulus_with(&KnownParams::default(), &to_bigints(values))
+}
+
+fn estimate_modulus_with(known: &KnownParams, values: &[BigInt]) -> Option<ModulusEstimate> {
+    let multiple = infer_modulus(known, values)?;
+    let consistent = refine_modulus(known, values, &multiple);
+    Some(ModulusEstimate {
+        modulus: consistent.first()?.clone(),
+        consistent,
+    })
+}
+
+/// Tries to derive LCG parameters based on known values
+///
+/// Accepts anything that converts into a [`BigInt`] and does all of the arithmetic in arbitrary precision, so 64-bit, 128-bit and RSA sized moduli work the same as small ones.
+///
+/// This is probabilistic and may be wrong, especially for low number of values, [`crack_lcg_ranked`] estimates how likely that is.
+/// When the differences between values share a factor with m several multipliers fit equally well and this returns the smallest, see [`crack_lcg_all`] for every one of them
+///
+/// [https://tailcall.net/blog/cracking-randomness-lcgs/](https://tailcall.net/blog/cracking-randomness-lcgs/)
+pub fn crack_lcg<T: Clone + Into<BigInt>>(values: &[T]) -> Option<LCG> {
+    crack_lcg_all(values).into_iter().next()
+}
+
+/// Same as [`crack_lcg`] but returns every (a, c) that fits instead of just one
+///
+/// The multiplier comes from solving `a*(x1 - x0) ≡ x2 - x1 (mod m)` for every consecutive difference at once, so nothing has to be invertible.
+/// If gcd(x1 - x0, m) = g then a is only pinned down mod m/g and all g candidates are returned, sorted by multiplier
+pub fn crack_lcg_all<T: Clone + Into<BigInt>>(values: &[T]) -> Vec<LCG> {
+    crack_lcg_with(&KnownParams::default(), values)
+}
+
+/// Chance that `zeroes` random multiples of m share a prime factor too big for [`refine_modulus`] to strip
+///
+/// That's the sum of p^-zeroes over primes p >= [`REFINE_BOUND`], approximated by an integral
+pub(crate) fn unstripped_factor_probability(zeroes: usize) -> f64 {
+    if zeroes < 2 {
+        return 1.0;
+    }
+    let bound = REFINE_BOUND as f64;
+    let k = zeroes as f64;
+    (bound.powf(1.0 - k) / ((k - 1.0) * bound.ln())).min(1.0)
+}
+
+/// Same as [`crack_lcg_with`] but ranks every candidate by how likely it is to be spurious
+///
+/// All (state, a, c) are taken to be equally likely a priori, so a candidate with modulus m explains the values with probability m^-3 and
+/// candidates are weighed against each other by that, which favours the smallest consistent modulus and splits evenly between multipliers.
+/// When m is inferred there's also the chance that the gcd still hides a big spurious factor, which shrinks quickly with every extra value
+pub fn crack_lcg_ranked<T: Clone + Into<BigInt>>(known: &KnownParams, values: &[T]) -> CrackResult {
+    let verified = values.len();
+    let values = with_state(known, values);
+    let (moduli, unstripped) = match &known.m {
+        Some(m) => (vec![m.clone()], 0.0),
+        None => {
+            let moduli = match estimate_modulus_with(known, &values) {
+                Some(estimate) => estimate.consistent,
+                None => return CrackResult::default(),
+            };
+            let unstripped =
+                unstripped_factor_probability(verified.saturating_sub(values_per_zero(known)));
+            (moduli, unstripped)
+        }
+    };
+
+    let lcgs = moduli
+        .iter()
+        .flat_map(|m| {
+            let known = KnownParams {
+                m: Some(m.clone()),
+                ..known.clone()
+            };
+            crack_values(&known, &values)
+        })
+        .collect::<Vec<_>>();
+    let smallest = match lcgs.iter().map(|lcg| &lcg.m).min() {
+        Some(m) => m.clone(),
+        None => return CrackResult::default(),
+    };
+    let weights = lcgs
+        .iter()
+        .map(|lcg| math::ratio(&smallest, &lcg.m).powi(3))
+        .collect::<Vec<_>>();
+    let total = weights.iter().sum::<f64>();
+    let mut candidates = izip!(lcgs, weights)
+        .map(|(lcg, weight)| Candidate {
+            lcg,
+            spurious: 1.0 - weight / total * (1.0 - unstripped),
+            verified,
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|x, y| {
+        x.spurious
+            .partial_cmp(&y.spurious)
+            .unwrap_or(Ordering::Equal)
+    });
+    CrackResult { candidates }
+}
+
+/// Same as [`crack_lcg`] but narrows the result into another integer backend
+///
+/// Returns None if cracking fails or the parameters don't fit in `T`
+pub fn crack_lcg_as<T: LcgInt>(values: &[impl Clone + Into<BigInt>]) -> Option<LCG<T>> {
+    crack_lcg(values)?.convert()
+}
+
+/// Derive the unknown LCG parameters when some of m, a and c are already known
+///
+/// Knowing parameters cuts down how many consecutive values are needed:
+/// with m, a and c known 1 value pins the state, with m and one of a or c known 2 values are enough, and with only m known it takes 3.
+/// A known seed in [`KnownParams::state`] counts as one of those, so it's 2 outputs with m known and 1 with a and m known.
+/// Without m it is inferred like in [`estimate_modulus`] which needs one more value than the above, and the smallest consistent modulus is used.
+///
+/// Returns every LCG consistent with all of the values, with the state set to the last value. This is more than one when the values share factors with m.
+/// Gives up and returns nothing if more than 2^16 multipliers fit, which only happens for degenerate input like a constant sequence
+pub fn crack_lcg_with<T: Clone + Into<BigInt>>(known: &KnownParams, values: &[T]) -> Vec<LCG> {
+    crack_values(known, &with_state(known, values))
+}
+
+/// [`crack_lcg_with`] once the known state is in front of the values, `known.state` itself is ignored
+fn crack_values(known: &KnownParams, values: &[BigInt]) -> Vec<LCG> {
+    let modulus = match &known.m {
+        Some(m) => m.clone(),
+        None => match estimate_modulus_with(known, values) {
+            Some(estimate) => estimate.modulus,
+            None => return vec![],
+        },
+    };
+    let last = match values.last() {
+        Some(last) => last.clone(),
+        None => return vec![],
+    };
+    if !modulus.is_positive() || values.iter().any(|x| x.is_negative() || *x >= modulus) {
+        return vec![];
+    }
+    // without a single equation for it every multiplier would fit
+    if known.a.is_none() && values.len() < if known.c.is_some() { 2 } else { 3 } {
+        return vec![];
+    }
+
+    let multipliers = match (&known.a, &known.c) {
+        (Some(a), _) => Some(vec![modulo(a, &modulus)]),
+        (None, Some(c)) => solve_multiplier(
+            izip!(values, values.iter().skip(1)).map(|(x, y)| (x, y - c)),
+            &modulus,
+        ),
+        (None, None) => {
+            let diffs = izip!(values, values.iter().skip(1))
+                .map(|(a, b)| b - a)
+                .collect::<Vec<_>>();
+            solve_multiplier(
+                izip!(&diffs, diffs.iter().skip(1)).map(|(x, y)| (x, y.clone())),
+                &modulus,
+            )
+        }
+    };
+
+    multipliers
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|a| {
+            let c = match &known.c {
+                Some(c) => modulo(c, &modulus),
+                None if values.len() >= 2 => modulo(&(&values[1] - &values[0] * &a), &modulus),
+                None => return None,
+            };
+            if fits(values, &a, &c, &modulus) {
+                Some(LCG {
+                    state: last.clone(),
+                    a,
+                    c,
+                    m: modulus.clone(),
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        crack_lcg, crack_lcg_all, crack_lcg_ranked, crack_lcg_with, estimate_modulus, KnownParams,
+        LCG,
+    };
+    use num::Zero;
+    use num_bigint::BigInt;
+
+    #[test]
+    fn it_cracks_wide_lcgs_without_overflow() {
+        // outputs are near 2^61 so the gcd terms are near 2^122 and would overflow any primitive
+        let m = (1u64 << 61) - 1;
+        let mut rand = LCG {
+            state: 0x0123_4567_89ab_cdefu64 % m,
+            a: 0x0fed_cba9_8765_4321 % m,
+            c: 0x1357_9bdf_2468_ace0 % m,
+            m,
+        };
+        let values = (&mut rand).take(10).collect::<Vec<u64>>();
+        assert_eq!(crack_lcg(&values), rand.convert::<BigInt>());
+
+        let m: BigInt = (BigInt::from(1) << 521) - 1;
+        let mut rand = LCG {
+            state: BigInt::from(0xdead_beefu64).pow(12) % &m,
+            a: BigInt::from(0x1234_5678u64).pow(15) % &m,
+            c: BigInt::from(0xabcd_ef01u64).pow(11) % &m,
+            m,
+        };
+        let values = (&mut rand).take(12).collect::<Vec<_>>();
+        assert_eq!(crack_lcg(&values), Some(rand));
+    }
+
+    #[test]
+    fn it_cracks_with_minimal_values_when_parameters_are_known() {
+        let params = LCG {
+            state: BigInt::from(32760),
+            a: BigInt::from(5039),
+            c: BigInt::from(76581),
+            m: BigInt::from(479001599),
+        };
+        let values = params.clone().take(6).collect::<Vec<_>>();
+        let known = |a: bool, c: bool, m: bool| KnownParams {
+            a: Some(params.a.clone()).filter(|_| a),
+            c: Some(params.c.clone()).filter(|_| c),
+            m: Some(params.m.clone()).filter(|_| m),
+            state: None,
+        };
+        let cracks = |known: KnownParams, count: usize| {
+            let expected = LCG {
+                state: values[count - 1].clone(),
+                ..params.clone()
+            };
+            crack_lcg_with(&known, &values[..count]) == vec![expected]
+        };
+        assert!(cracks(known(false, false, true), 3));
+        assert!(cracks(known(true, false, true), 2));
+        assert!(cracks(known(false, true, true), 2));
+        assert!(cracks(known(true, true, true), 1));
+        assert!(!cracks(known(true, false, true), 1));
+        assert!(!cracks(known(false, false, true), 2));
+
+        // without m a and c still save values over crack_lcg
+        assert!(cracks(known(true, true, false), 4));
+        assert!(cracks(known(true, false, false), 5));
+
+        // a known seed saves one more, down to 2 outputs with m known and 1 with a and m known
+        let seeded = |a: bool, m: bool| KnownParams {
+            state: Some(params.state.clone()),
+            ..known(a, false, m)
+        };
+        assert!(cracks(seeded(false, true), 2));
+        assert!(cracks(seeded(true, true), 1));
+        assert!(!cracks(seeded(false, true), 1));
+        assert!(cracks(seeded(true, false), 4));
+    }
+
+    #[test]
+    fn it_lists_every_multiplier_when_values_share_factors_with_m() {
+        // 6*a ≡ 33 - 3 (mod 64) has two solutions since gcd(6, 64) = 2
+        let known = KnownParams {
+            c: Some(BigInt::from(3)),
+            m: Some(BigInt::from(64)),
+            ..KnownParams::default()
+        };
+        let cracked = crack_lcg_with(&known, &[6, 33]);
+        assert_eq!(
+            cracked.iter().map(|x| x.a.clone()).collect::<Vec<_>>(),
+            vec![BigInt::from(5), BigInt::from(37)]
+        );
+    }
+
+    #[test]
+    fn it_cracks_when_no_difference_is_invertible() {
+        // odd a and even c keep every difference even mod 2^32
+        let mut rand = LCG {
+            state: 0x1234_5678u64,
+            a: 1103515245,
+            c: 12346,
+            m: 1 << 32,
+        };
+        let values = (&mut rand).take(12).collect::<Vec<_>>();
+        let cracked = crack_lcg_all(&values);
+        assert!(cracked.len() > 1);
+        assert!(cracked.contains(&rand.convert().unwrap()));
+        for lcg in &cracked {
+            let replay = LCG {
+                state: BigInt::from(values[0]),
+                ..lcg.clone()
+            };
+            assert_eq!(
+                replay.take(11).collect::<Vec<_>>(),
+                values[1..]
+                    .iter()
+                    .map(|&x| BigInt::from(x))
+                    .collect::<Vec<_>>()
+            );
+        }
+        assert_eq!(crack_lcg(&values).as_ref(), cracked.first());
+    }
+
+    #[test]
+    fn it_strips_spurious_factors_from_the_modulus() {
+        let mut rand = LCG {
+            state: 22u64,
+            a: 48271,
+            c: 0,
+            m: 2147483647,
+        };
+        // the raw gcd of these is 280 * m
+        let values = (&mut rand).take(5).collect::<Vec<_>>();
+        let estimate = estimate_modulus(&values).unwrap();
+        assert_eq!(estimate.modulus, BigInt::from(rand.m));
+        assert!(estimate.is_ambiguous());
+        assert!(estimate.consistent.iter().all(|m| (m % rand.m).is_zero()));
+        assert_eq!(crack_lcg(&values), rand.convert());
+
+        let values = (&mut rand).take(10).collect::<Vec<_>>();
+        assert!(!estimate_modulus(&values).unwrap().is_ambiguous());
+    }
+
+    #[test]
+    fn it_ranks_candidates_by_confidence() {
+        let mut rand = LCG {
+            state: 22u64,
+            a: 48271,
+            c: 0,
+            m: 2147483647,
+        };
+        let values = (&mut rand).take(5).collect::<Vec<_>>();
+        let few = crack_lcg_ranked(&KnownParams::default(), &values);
+        assert_eq!(few.best().unwrap().lcg, rand.convert().unwrap());
+        assert!(few.candidates.len() > 1);
+
+        let values = (&mut rand).take(10).collect::<Vec<_>>();
+        let many = crack_lcg_ranked(&KnownParams::default(), &values);
+        assert!(many.is_unique());
+        assert_eq!(many.best().unwrap().verified, 10);
+        assert!(many.best().unwrap().spurious < 1e-9);
+        assert!(many.best().unwrap().spurious < few.best().unwrap().spurious);
+
+        // both multipliers fit [6, 33] mod 64 equally well
+        let known = KnownParams {
+            c: Some(3.into()),
+            m: Some(64.into()),
+            ..KnownParams::default()
+        };
+        let split = crack_lcg_ranked(&known, &[6, 33]);
+        assert_eq!(split.candidates.len(), 2);
+        assert!(split.candidates.iter().all(|x| x.spurious == 0.5));
+    }
+
+    #[test]
+    fn it_refuses_too_few_or_degenerate_values() {
+        assert_eq!(crack_lcg(&[1, 2, 3]), None);
+        assert_eq!(crack_lcg(&[5u8, 5, 5, 5, 5]), None);
+    }
+}
@@ -0,0 +1,52 @@
+use std::fmt;
+
+/// Errors produced when an operation can't be carried out with the given LCG parameters
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum LcgError {
+    /// The multiplier shares a factor with the modulus so the LCG can't be stepped backwards
+    NonInvertibleMultiplier,
+    /// The modulus isn't a power of two that fits in the requested integer type
+    ModulusNotPowerOfTwo,
+    /// The modulus is zero or negative
+    NonPositiveModulus,
+    /// The modulus is even, which Montgomery reduction can't work with
+    EvenModulus,
+    /// A string parameter isn't a valid decimal or `0x` prefixed hex number
+    InvalidNumber(String),
+    /// A required parameter was never given to the builder
+    MissingParameter(&'static str),
+    /// A lattice basis is empty, has rows of different lengths or isn't linearly independent
+    InvalidBasis,
+}
+
+impl fmt::Display for LcgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LcgError::NonInvertibleMultiplier => write!(
+                f,
+                "multiplier is not invertible mod m (gcd(a, m) != 1) so the LCG can't be stepped backwards"
+            ),
+            LcgError::ModulusNotPowerOfTwo => write!(
+                f,
+                "modulus is not a power of two that fits in the requested integer type"
+            ),
+            LcgError::NonPositiveModulus => write!(f, "modulus must be positive"),
+            LcgError::EvenModulus => write!(
+                f,
+                "modulus must be odd for Montgomery reduction, PowerOfTwoLCG covers powers of two"
+            ),
+            LcgError::InvalidNumber(input) => write!(
+                f,
+                "{:?} is not a decimal or 0x prefixed hex number",
+                input
+            ),
+            LcgError::MissingParameter(name) => write!(f, "{} was never set", name),
+            LcgError::InvalidBasis => write!(
+                f,
+                "lattice basis must be non-empty, rectangular and linearly independent"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LcgError {}
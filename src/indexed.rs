@@ -0,0 +1,261 @@
+//! Cracking from outputs at known but not necessarily consecutive positions, see [`crack_lcg_indexed`]
+
+use crate::crack::{candidate_moduli, MAX_CANDIDATES};
+use crate::{affine_pow, math, modulo, poly, KnownParams, LCG};
+use num::{Integer, One, Signed, ToPrimitive, Zero};
+use num_bigint::BigInt;
+
+/// Inferring m takes resultants of polynomials whose degree is the distance spanned by three outputs, or powers of a known a that high,
+/// so anything further apart than this is only used once m is known
+const MAX_RESULTANT_DEGREE: u64 = 64;
+
+/// At most this many polynomials are paired up for resultants
+const MAX_RESULTANT_POLYNOMIALS: usize = 5;
+
+/// `(y_t - y_0)*S_s(a) - (y_s - y_0)*S_t(a)` for outputs at relative indices 0, s and t, where `S_n(a) = 1 + a + ... + a^(n-1)`, which is 0 mod m at the real a
+///
+/// Every output is `y_t = y_0 + S_t(a)*(y_1 - y_0)` so this holds whether or not anything is invertible
+fn relation(s: u64, y_s: &BigInt, t: u64, y_t: &BigInt, y_0: &BigInt) -> Vec<BigInt> {
+    (0..t)
+        .map(|i| if i < s { y_t - y_s } else { y_0 - y_s })
+        .collect()
+}
+
+/// `S_n(a)` over the integers, so n should be small
+fn geometric_sum(a: &BigInt, n: u64) -> BigInt {
+    (0..n).fold(BigInt::zero(), |sum, _| sum * a + 1u32)
+}
+
+/// `(a^n, S_n(a))` mod m
+fn jump_coefficients(a: &BigInt, n: u64, m: &BigInt) -> (BigInt, BigInt) {
+    affine_pow(a, &BigInt::one(), &BigInt::from(n), m)
+}
+
+/// gcd of resultants between pairs of relations, or of the relations evaluated at a when it is known, which is a multiple of m
+fn infer_modulus(
+    known: &KnownParams,
+    outputs: &[(u64, BigInt)],
+    relations: &[(u64, Vec<BigInt>)],
+) -> Option<BigInt> {
+    let modulus = match &known.a {
+        // with a known every relation evaluates to a multiple of m, no need to write it out
+        Some(a) => outputs
+            .windows(3)
+            .filter(|w| w[2].0 - w[0].0 <= MAX_RESULTANT_DEGREE)
+            .map(|w| {
+                let ((b, y_0), (s, y_s), (t, y_t)) = (&w[0], &w[1], &w[2]);
+                (y_t - y_0) * geometric_sum(a, s - b) - (y_s - y_0) * geometric_sum(a, t - b)
+            })
+            .fold(BigInt::zero(), |sum, x| sum.gcd(&x)),
+        None => {
+            let small = relations
+                .iter()
+                .filter(|(t, _)| *t <= MAX_RESULTANT_DEGREE)
+                .take(MAX_RESULTANT_POLYNOMIALS)
+                .collect::<Vec<_>>();
+            let mut modulus = BigInt::zero();
+            for (i, (_, p)) in small.iter().enumerate() {
+                for (_, q) in &small[i + 1..] {
+                    modulus = modulus.gcd(&poly::resultant(p, q));
+                }
+            }
+            modulus
+        }
+    };
+    if modulus.is_zero() {
+        None
+    } else {
+        Some(modulus)
+    }
+}
+
+/// Every LCG mod m reproducing the outputs, with the state set to the output at `base`
+fn crack_with_modulus(
+    known: &KnownParams,
+    outputs: &[(u64, BigInt)],
+    relations: &[(u64, Vec<BigInt>)],
+    m: &BigInt,
+) -> Vec<LCG> {
+    let (base, y_0) = &outputs[0];
+    if outputs.iter().any(|(_, y)| y.is_negative() || y >= m) {
+        return vec![];
+    }
+    let a = match &known.a {
+        Some(a) => modulo(a, m),
+        None => {
+            let mut common = match relations.first() {
+                Some((_, p)) => p.clone(),
+                None => return vec![],
+            };
+            for (_, q) in &relations[1..] {
+                common = match poly::gcd_mod(&common, q, m) {
+                    Some(common) => common,
+                    None => return vec![],
+                };
+            }
+            // several roots means several multipliers fit, which is left to the caller to resolve with more outputs
+            if common.len() != 2 {
+                return vec![];
+            }
+            modulo(&-&common[0], m)
+        }
+    };
+
+    let increments = match (&known.c, outputs.get(1)) {
+        (Some(c), _) => vec![modulo(c, m)],
+        (None, Some((t, y_t))) => {
+            let (a_n, s_n) = jump_coefficients(&a, t - base, m);
+            match math::solve_linear(&s_n, &(y_t - a_n * y_0), m) {
+                Some((first, step)) => {
+                    let count = (m / &step).to_usize().unwrap_or(usize::MAX);
+                    if count > MAX_CANDIDATES {
+                        return vec![];
+                    }
+                    (0..count).map(|k| &first + &step * k).collect()
+                }
+                None => vec![],
+            }
+        }
+        (None, None) => return vec![],
+    };
+
+    increments
+        .into_iter()
+        .filter(|c| {
+            outputs.iter().all(|(t, y)| {
+                let (a_n, c_n) = affine_pow(&a, c, &BigInt::from(t - base), m);
+                modulo(&(a_n * y_0 + c_n), m) == *y
+            })
+        })
+        .map(|c| LCG {
+            state: y_0.clone(),
+            a: a.clone(),
+            c,
+            m: m.clone(),
+        })
+        .collect()
+}
+
+/// Crack an LCG from `(index, value)` pairs where other consumers took outputs in between, such as outputs 0, 3, 7 and 20
+///
+/// Every output is `y_t = y_0 + S_t(a)*(y_1 - y_0)` with `S_t(a) = 1 + a + ... + a^(t-1)`, the jump-ahead form of the affine map.
+/// Eliminating the unknown step from every three consecutive outputs leaves polynomials in a which all vanish mod m.
+/// Without m, resultants of those polynomials are multiples of m and their gcd is refined like in [`estimate_modulus`](crate::estimate_modulus), which takes at least 5 outputs and a few more when the indices are far apart.
+/// Once m is known a is the common root of the polynomials and c follows from the first gap.
+///
+/// Returns every LCG reproducing all of the outputs with the state set to the output at index 0, rewinding when the first index isn't 0.
+/// Gives up when several multipliers fit, or when a rewind isn't unique because a isn't invertible
+pub fn crack_lcg_indexed<T: Clone + Into<BigInt>>(
+    known: &KnownParams,
+    outputs: &[(u64, T)],
+) -> Vec<LCG> {
+    let mut outputs = outputs
+        .iter()
+        .map(|(t, y)| (*t, y.clone().into()))
+        .collect::<Vec<(u64, BigInt)>>();
+    outputs.sort();
+    outputs.dedup();
+    if outputs.windows(2).any(|pair| pair[0].0 == pair[1].0) || outputs.is_empty() {
+        return vec![];
+    }
+    let base = outputs[0].0;
+    // consecutive triples keep the degrees low, and resultants between relations with different bases don't share spurious factors.
+    // They're only needed to find an unknown a, a known one jumps straight to any index
+    let relations = match known.a {
+        Some(_) => vec![],
+        None => outputs
+            .windows(3)
+            .map(|w| {
+                let (b, s, t) = (&w[0], &w[1], &w[2]);
+                (t.0 - b.0, relation(s.0 - b.0, &s.1, t.0 - b.0, &t.1, &b.1))
+            })
+            .collect::<Vec<_>>(),
+    };
+
+    let moduli = match &known.m {
+        Some(m) => vec![m.clone()],
+        None => match infer_modulus(known, &outputs, &relations) {
+            Some(multiple) => {
+                let max = outputs.iter().map(|(_, y)| y).max().cloned();
+                candidate_moduli(&multiple, &max.unwrap_or_default())
+            }
+            None => return vec![],
+        },
+    };
+    let lcgs = moduli
+        .iter()
+        .map(|m| crack_with_modulus(known, &outputs, &relations, m))
+        .find(|lcgs| !lcgs.is_empty())
+        .unwrap_or_default();
+
+    lcgs.into_iter()
+        .filter_map(|mut lcg| {
+            lcg.jump(&-BigInt::from(base)).ok()?;
+            Some(lcg)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{crack_lcg_indexed, KnownParams, LCG};
+    use num_bigint::BigInt;
+
+    fn leak(rand: &LCG<u64>, indices: &[u64]) -> Vec<(u64, u64)> {
+        indices
+            .iter()
+            .map(|&i| {
+                let mut rand = rand.clone();
+                (i, LCG::skip(&mut rand, &BigInt::from(i)))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_cracks_from_gapped_outputs() {
+        let rand = LCG {
+            state: 123456789u64,
+            a: 48271,
+            c: 11,
+            m: 2147483647,
+        };
+        let outputs = leak(&rand, &[0, 3, 7, 20, 22, 30]);
+        let cracked = crack_lcg_indexed(&KnownParams::default(), &outputs);
+        let expected = rand.convert::<BigInt>().unwrap();
+        assert_eq!(cracked, vec![expected.clone()]);
+
+        // a known modulus works with far apart outputs, and the first one doesn't have to be at 0
+        let outputs = leak(&rand, &[5, 7, 500, 1003]);
+        let known = KnownParams {
+            m: Some(BigInt::from(rand.m)),
+            ..KnownParams::default()
+        };
+        assert_eq!(crack_lcg_indexed(&known, &outputs), vec![expected.clone()]);
+    }
+
+    #[test]
+    fn it_jumps_to_huge_indices_with_a_known() {
+        let rand = LCG {
+            state: 987654321u64,
+            a: 48271,
+            c: 11,
+            m: 2147483647,
+        };
+        let expected = rand.convert::<BigInt>().unwrap();
+        let outputs = leak(&rand, &[1 << 40, 3 << 50, u64::MAX / 3]);
+        let known = KnownParams {
+            a: Some(BigInt::from(rand.a)),
+            m: Some(BigInt::from(rand.m)),
+            ..KnownParams::default()
+        };
+        assert_eq!(crack_lcg_indexed(&known, &outputs), vec![expected.clone()]);
+
+        // m comes from the outputs close together, the far one only has to fit
+        let outputs = leak(&rand, &[0, 2, 5, 9, 11, 14, 1 << 62]);
+        let known = KnownParams {
+            a: Some(BigInt::from(rand.a)),
+            ..KnownParams::default()
+        };
+        assert_eq!(crack_lcg_indexed(&known, &outputs), vec![expected]);
+    }
+}
@@ -0,0 +1,96 @@
+//! Integer backends an [`LCG`](crate::LCG) can be stored in
+
+use crate::modulo;
+use num::ToPrimitive;
+use num_bigint::BigInt;
+use std::fmt::Debug;
+
+/// Integer type usable as the state and parameters of an [`LCG`](crate::LCG)
+///
+/// Primitive widths keep stepping allocation free, everything that isn't plain stepping goes through [`BigInt`]
+pub trait LcgInt: Clone + Debug + Eq {
+    /// `(x * a + c) mod m` without overflowing the backing type
+    fn mul_add_mod(x: &Self, a: &Self, c: &Self, m: &Self) -> Self;
+
+    /// Widen to a BigInt
+    fn to_big(&self) -> BigInt;
+
+    /// Narrow a BigInt, returns None if it doesn't fit
+    fn from_big(value: &BigInt) -> Option<Self>;
+}
+
+impl LcgInt for BigInt {
+    fn mul_add_mod(x: &Self, a: &Self, c: &Self, m: &Self) -> Self {
+        modulo(&(x * a + c), m)
+    }
+
+    fn to_big(&self) -> BigInt {
+        self.clone()
+    }
+
+    fn from_big(value: &BigInt) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+/// Primitive widths which can do the multiply in a type twice as wide
+macro_rules! impl_lcg_int {
+    ($t:ty, $wide:ty, $to:ident) => {
+        impl LcgInt for $t {
+            fn mul_add_mod(x: &Self, a: &Self, c: &Self, m: &Self) -> Self {
+                let wide = <$wide>::from(*x) * <$wide>::from(*a) + <$wide>::from(*c);
+                (wide % <$wide>::from(*m)) as $t
+            }
+
+            fn to_big(&self) -> BigInt {
+                BigInt::from(*self)
+            }
+
+            fn from_big(value: &BigInt) -> Option<Self> {
+                value.$to()
+            }
+        }
+    };
+}
+
+impl_lcg_int!(u32, u64, to_u32);
+impl_lcg_int!(u64, u128, to_u64);
+
+/// `(x + y) mod m` for x, y < m without overflowing
+fn add_mod_u128(x: u128, y: u128, m: u128) -> u128 {
+    let (sum, overflow) = x.overflowing_add(y);
+    if overflow || sum >= m {
+        sum.wrapping_sub(m)
+    } else {
+        sum
+    }
+}
+
+impl LcgInt for u128 {
+    fn mul_add_mod(x: &Self, a: &Self, c: &Self, m: &Self) -> Self {
+        let (mut x, mut a) = (x % m, a % m);
+        let product = if x.leading_zeros() + a.leading_zeros() >= 128 {
+            x * a % m
+        } else {
+            // nothing wider to multiply in so fall back to double-and-add
+            let mut product = 0;
+            while a > 0 {
+                if a & 1 == 1 {
+                    product = add_mod_u128(product, x, *m);
+                }
+                x = add_mod_u128(x, x, *m);
+                a >>= 1;
+            }
+            product
+        };
+        add_mod_u128(product, c % m, *m)
+    }
+
+    fn to_big(&self) -> BigInt {
+        BigInt::from(*self)
+    }
+
+    fn from_big(value: &BigInt) -> Option<Self> {
+        value.to_u128()
+    }
+}
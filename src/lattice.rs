@@ -0,0 +1,502 @@
+//! Lattice reduction over [`BigInt`] for the attacks where only part of each state leaks, see [`lll`] and [`bkz`]
+//!
+//! A basis is a list of rows, every routine returns a basis of the same lattice.
+//! Gram-Schmidt is kept as exact integers like in Cohen's integral LLL (algorithm 2.6.7 in A Course in Computational Algebraic Number Theory) so nothing is lost to rounding however big the entries get
+
+use crate::math::{self, ratio};
+use crate::LcgError;
+use num::{Integer, One, Signed, Zero};
+use num_bigint::BigInt;
+
+/// Lovász constant used by [`lll`] and [`bkz`], as a fraction
+const DELTA: (u32, u32) = (99, 100);
+
+/// [`bkz`] stops after this many tours even if they are still improving the basis
+const MAX_TOURS: usize = 16;
+
+/// Enumerating one block gives up after this many nodes and keeps the best vector so far
+const MAX_NODES: usize = 1 << 20;
+
+fn dot(x: &[BigInt], y: &[BigInt]) -> BigInt {
+    x.iter().zip(y).map(|(a, b)| a * b).sum()
+}
+
+/// `x / y` rounded to the nearest integer for positive y
+fn round_div(x: &BigInt, y: &BigInt) -> BigInt {
+    (x * 2u32 + y).div_floor(&(y * 2u32))
+}
+
+/// A basis along with its Gram-Schmidt data as integers
+struct Integral {
+    basis: Vec<Vec<BigInt>>,
+    /// `d[i]` is the Gram determinant of the first i rows, `d[0] = 1`, so row i's squared Gram-Schmidt norm is `d[i+1]/d[i]`
+    d: Vec<BigInt>,
+    /// `lambda[k][j] = d[j+1] * mu_kj` for j < k
+    lambda: Vec<Vec<BigInt>>,
+}
+
+impl Integral {
+    fn new(basis: &[Vec<BigInt>]) -> Result<Integral, LcgError> {
+        let n = basis.len();
+        let width = basis.first().map_or(0, Vec::len);
+        if n == 0 || width == 0 || basis.iter().any(|row| row.len() != width) {
+            return Err(LcgError::InvalidBasis);
+        }
+        let mut d = vec![BigInt::one(); n + 1];
+        let mut lambda = vec![vec![BigInt::zero(); n]; n];
+        for k in 0..n {
+            for j in 0..=k {
+                let mut u = dot(&basis[k], &basis[j]);
+                for i in 0..j {
+                    u = (&d[i + 1] * u - &lambda[k][i] * &lambda[j][i]) / &d[i];
+                }
+                if j < k {
+                    lambda[k][j] = u;
+                } else if u.is_zero() {
+                    return Err(LcgError::InvalidBasis);
+                } else {
+                    d[k + 1] = u;
+                }
+            }
+        }
+        Ok(Integral {
+            basis: basis.to_vec(),
+            d,
+            lambda,
+        })
+    }
+
+    /// Size reduce row k against row l
+    fn reduce(&mut self, k: usize, l: usize) {
+        if &self.lambda[k][l].abs() * 2 <= self.d[l + 1] {
+            return;
+        }
+        let q = round_div(&self.lambda[k][l], &self.d[l + 1]);
+        let (head, tail) = self.basis.split_at_mut(k);
+        for (x, y) in tail[0].iter_mut().zip(&head[l]) {
+            *x -= &q * y;
+        }
+        self.lambda[k][l] -= &q * &self.d[l + 1];
+        for i in 0..l {
+            let step = &q * &self.lambda[l][i];
+            self.lambda[k][i] -= step;
+        }
+    }
+
+    /// Swap rows k-1 and k and update the Gram-Schmidt data to match
+    fn swap(&mut self, k: usize) {
+        self.basis.swap(k, k - 1);
+        for j in 0..k - 1 {
+            let (head, tail) = self.lambda.split_at_mut(k);
+            std::mem::swap(&mut head[k - 1][j], &mut tail[0][j]);
+        }
+        let lambda = self.lambda[k][k - 1].clone();
+        let b = (&self.d[k - 1] * &self.d[k + 1] + &lambda * &lambda) / &self.d[k];
+        for i in k + 1..self.basis.len() {
+            let t = self.lambda[i][k].clone();
+            self.lambda[i][k] =
+                (&self.d[k + 1] * &self.lambda[i][k - 1] - &lambda * &t) / &self.d[k];
+            self.lambda[i][k - 1] = (&b * t + &lambda * &self.lambda[i][k]) / &self.d[k + 1];
+        }
+        self.d[k] = b;
+    }
+
+    fn lll(&mut self) {
+        let (p, q) = (BigInt::from(DELTA.0), BigInt::from(DELTA.1));
+        let mut k = 1;
+        while k < self.basis.len() {
+            self.reduce(k, k - 1);
+            let lambda = &self.lambda[k][k - 1];
+            // Lovász condition B_k >= (δ - μ²) B_(k-1) multiplied through by d[k]^2 / B_(k-1)
+            if &q * &self.d[k + 1] * &self.d[k - 1]
+                < &p * &self.d[k] * &self.d[k] - &q * lambda * lambda
+            {
+                self.swap(k);
+                k = (k - 1).max(1);
+            } else {
+                for l in (0..k - 1).rev() {
+                    self.reduce(k, l);
+                }
+                k += 1;
+            }
+        }
+    }
+
+    /// Squared Gram-Schmidt norms of rows `start..end` relative to row `start` and the μ between them
+    fn block(&self, start: usize, end: usize) -> (Vec<f64>, Vec<Vec<f64>>) {
+        let first = (&self.d[start + 1], &self.d[start]);
+        let norms = (start..end)
+            .map(|i| ratio(&(&self.d[i + 1] * first.1), &(&self.d[i] * first.0)))
+            .collect();
+        let mu = (start..end)
+            .map(|i| {
+                (start..i)
+                    .map(|j| ratio(&self.lambda[i][j], &self.d[j + 1]))
+                    .collect()
+            })
+            .collect();
+        (norms, mu)
+    }
+}
+
+/// Shortest nonzero combination of a block, Schnorr-Euchner style depth first search
+struct Enumeration<'a> {
+    norms: &'a [f64],
+    mu: &'a [Vec<f64>],
+    x: Vec<i64>,
+    best: Option<Vec<i64>>,
+    radius: f64,
+    nodes: usize,
+}
+
+impl Enumeration<'_> {
+    fn search(&mut self, level: usize, distance: f64) {
+        let n = self.norms.len();
+        let center = -(level + 1..n)
+            .map(|j| self.mu[j][level] * self.x[j] as f64)
+            .sum::<f64>();
+        // only one of x and -x is needed, so the top nonzero coefficient is kept positive
+        let top = self.x[level + 1..].iter().all(|&x| x == 0);
+        let spread = ((self.radius - distance) / self.norms[level])
+            .max(0.0)
+            .sqrt();
+        let low = (center - spread).ceil() as i64;
+        let high = (center + spread).floor() as i64;
+        if high.saturating_sub(low) as usize > MAX_NODES {
+            return;
+        }
+        let mut candidates = (if top { low.max(0) } else { low }..=high).collect::<Vec<_>>();
+        candidates.sort_by(|a, b| {
+            let (a, b) = ((*a as f64 - center).abs(), (*b as f64 - center).abs());
+            a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+        });
+        for value in candidates {
+            self.nodes += 1;
+            if self.nodes > MAX_NODES {
+                return;
+            }
+            let offset = value as f64 - center;
+            let distance = distance + self.norms[level] * offset * offset;
+            if distance >= self.radius {
+                continue;
+            }
+            self.x[level] = value;
+            if level > 0 {
+                self.search(level - 1, distance);
+            } else if self.x.iter().any(|&x| x != 0) {
+                self.radius = distance;
+                self.best = Some(self.x.clone());
+            }
+        }
+        self.x[level] = 0;
+    }
+}
+
+/// Make `sum(coefficients[i] * basis[start + i])` row `start` with unimodular row operations
+///
+/// Runs Euclid's algorithm on the coefficients, returns false if they aren't coprime
+fn insert(basis: &mut [Vec<BigInt>], start: usize, coefficients: &[i64]) -> bool {
+    let mut x = coefficients.to_vec();
+    loop {
+        let mut nonzero = (0..x.len()).filter(|&i| x[i] != 0).collect::<Vec<_>>();
+        nonzero.sort_by_key(|&i| x[i].abs());
+        match nonzero.as_slice() {
+            [] => return false,
+            [only] => {
+                if x[*only].abs() != 1 {
+                    return false;
+                }
+                if x[*only] < 0 {
+                    basis[start + only].iter_mut().for_each(|v| *v = -&*v);
+                }
+                basis[start..=start + only].rotate_right(1);
+                return true;
+            }
+            [small, rest @ ..] => {
+                // x_i b_i + x_j b_j == (x_i - q x_j) b_i + x_j (b_i q + b_j)
+                let (j, i) = (*small, rest[rest.len() - 1]);
+                let q = x[i] / x[j];
+                x[i] -= q * x[j];
+                let row = basis[start + i].clone();
+                for (v, w) in basis[start + j].iter_mut().zip(&row) {
+                    *v += w * q;
+                }
+            }
+        }
+    }
+}
+
+/// LLL reduce a basis with δ = 0.99
+///
+/// The first row of the result is at most 2^((n-1)/2) times longer than the shortest vector in the lattice and is usually much closer than that.
+/// Fails with [`LcgError::InvalidBasis`] if the rows are ragged or linearly dependent
+pub fn lll(basis: &[Vec<BigInt>]) -> Result<Vec<Vec<BigInt>>, LcgError> {
+    let mut integral = Integral::new(basis)?;
+    integral.lll();
+    Ok(integral.basis)
+}
+
+/// BKZ reduce a basis with blocks of `block_size` rows, stronger than [`lll`] at the cost of enumerating each block
+///
+/// This is the textbook version without pruning, so keep blocks to about 20 rows or fewer. A block size of 2 or less is plain LLL
+pub fn bkz(basis: &[Vec<BigInt>], block_size: usize) -> Result<Vec<Vec<BigInt>>, LcgError> {
+    let mut integral = Integral::new(basis)?;
+    integral.lll();
+    let n = integral.basis.len();
+    for _ in 0..MAX_TOURS {
+        let mut improved = false;
+        for start in 0..n.saturating_sub(1) {
+            let end = (start + block_size).min(n);
+            if end - start < 2 {
+                continue;
+            }
+            let (norms, mu) = integral.block(start, end);
+            let mut enumeration = Enumeration {
+                norms: &norms,
+                mu: &mu,
+                x: vec![0; end - start],
+                best: None,
+                // norms are relative to the current first row, so anything found is an improvement
+                radius: f64::from(DELTA.0) / f64::from(DELTA.1),
+                nodes: 0,
+            };
+            enumeration.search(end - start - 1, 0.0);
+            if let Some(best) = enumeration.best {
+                let mut basis = integral.basis.clone();
+                if insert(&mut basis, start, &best) {
+                    integral = Integral::new(&basis)?;
+                    integral.lll();
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+    Ok(integral.basis)
+}
+
+/// Block size [`solve_bounded`] falls back to when [`lll`] alone doesn't find the solution
+const FALLBACK_BLOCK: usize = 10;
+
+/// For each congruence, an unknown with an invertible coefficient that no other congruence uses, along with the inverse
+fn pivots(rows: &[Vec<BigInt>], m: &BigInt) -> Vec<Option<(usize, BigInt)>> {
+    let mut taken = vec![false; rows.first().map_or(0, Vec::len)];
+    rows.iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let (j, inverse) = (0..row.len()).find_map(|j| {
+                let alone = rows
+                    .iter()
+                    .enumerate()
+                    .all(|(other, r)| other == i || r[j].mod_floor(m).is_zero());
+                if taken[j] || !alone {
+                    return None;
+                }
+                Some((j, math::inverse(&row[j].mod_floor(m), m)?))
+            })?;
+            taken[j] = true;
+            Some((j, inverse))
+        })
+        .collect()
+}
+
+/// Find `z` with `0 <= z[j] < bounds[j]` and `sum(rows[i][j] * z[j]) ≡ targets[i] mod m` for every i, if the bounds make it unique enough to be the shortest thing around
+///
+/// Each unknown is centred and scaled up to the biggest bound and the targets go in with Kannan's embedding.
+/// A congruence with an unknown all to itself and invertible coefficient is solved for that unknown, any other gets a heavily weighted column so vectors breaking it are long.
+/// Works when the product of the bounds is well below `m^rows.len()`. Returns None when reduction doesn't turn up a solution, anything returned satisfies every constraint
+pub fn solve_bounded(
+    rows: &[Vec<BigInt>],
+    targets: &[BigInt],
+    m: &BigInt,
+    bounds: &[BigInt],
+) -> Option<Vec<BigInt>> {
+    let k = bounds.len();
+    if k == 0
+        || !m.is_positive()
+        || targets.len() != rows.len()
+        || rows.iter().any(|row| row.len() != k)
+        || bounds.iter().any(|bound| !bound.is_positive())
+    {
+        return None;
+    }
+    let centres = bounds.iter().map(|bound| bound / 2u32).collect::<Vec<_>>();
+    let radii = bounds
+        .iter()
+        .zip(&centres)
+        .map(|(bound, centre)| (bound - centre).max(BigInt::one()))
+        .collect::<Vec<_>>();
+    let scale = radii.iter().max()?.clone();
+    let weights = radii.iter().map(|r| &scale / r).collect::<Vec<_>>();
+    let heavy = &scale * (4 * (k + 2));
+    let shifted = rows
+        .iter()
+        .zip(targets)
+        .map(|(row, target)| (target - dot(row, &centres)).mod_floor(m))
+        .collect::<Vec<_>>();
+    let pivots = pivots(rows, m);
+    let pivot_of = |j: usize| pivots.iter().flatten().any(|(p, _)| *p == j);
+    let heavy_rows = (0..rows.len())
+        .filter(|&i| pivots[i].is_none())
+        .collect::<Vec<_>>();
+    let e = heavy_rows.len();
+
+    let width = k + e + 1;
+    let mut basis = vec![];
+    for j in 0..k {
+        let mut row = vec![BigInt::zero(); width];
+        if pivot_of(j) {
+            row[j] = &weights[j] * m;
+        } else {
+            row[j] = weights[j].clone();
+            // a pivot moves by minus its congruence's coefficient over its own whenever this unknown moves by one
+            for (i, pivot) in pivots.iter().enumerate() {
+                if let Some((p, inverse)) = pivot {
+                    row[*p] = (-&rows[i][j] * inverse).mod_floor(m) * &weights[*p];
+                }
+            }
+            for (column, &i) in heavy_rows.iter().enumerate() {
+                row[k + column] = rows[i][j].mod_floor(m) * &heavy;
+            }
+        }
+        basis.push(row);
+    }
+    for column in 0..e {
+        let mut row = vec![BigInt::zero(); width];
+        row[k + column] = m * &heavy;
+        basis.push(row);
+    }
+    let mut embedding = vec![BigInt::zero(); width];
+    for (i, pivot) in pivots.iter().enumerate() {
+        if let Some((p, inverse)) = pivot {
+            embedding[*p] = (&shifted[i] * inverse).mod_floor(m) * &weights[*p];
+        }
+    }
+    for (column, &i) in heavy_rows.iter().enumerate() {
+        embedding[k + column] = -&shifted[i] * &heavy;
+    }
+    embedding[k + e] = scale.clone();
+    basis.push(embedding);
+
+    let check = |reduced: &[Vec<BigInt>]| {
+        reduced.iter().find_map(|v| {
+            if v[k..k + e].iter().any(|x| !x.is_zero()) || v[k + e].abs() != scale {
+                return None;
+            }
+            let sign: i32 = if v[k + e].is_negative() { -1 } else { 1 };
+            let z = (0..k)
+                .map(|j| {
+                    let (q, r) = (&v[j] * sign).div_rem(&weights[j]);
+                    if r.is_zero() {
+                        Some(q + &centres[j])
+                    } else {
+                        None
+                    }
+                })
+                .collect::<Option<Vec<_>>>()?;
+            let fits = z
+                .iter()
+                .zip(bounds)
+                .all(|(x, bound)| !x.is_negative() && x < bound)
+                && rows
+                    .iter()
+                    .zip(targets)
+                    .all(|(row, target)| (dot(row, &z) - target).mod_floor(m).is_zero());
+            if fits {
+                Some(z)
+            } else {
+                None
+            }
+        })
+    };
+    let reduced = lll(&basis).ok()?;
+    check(&reduced).or_else(|| check(&bkz(&reduced, FALLBACK_BLOCK).ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dot, Integral, DELTA};
+    use crate::{bkz, lll, LcgError};
+    use num::{Integer, Signed, Zero};
+    use num_bigint::BigInt;
+
+    /// Rows of the lattice {x : sum(weights[i] * x[i]) ≡ 0 mod m}, weights[0] has to be invertible
+    fn kernel(weights: &[BigInt], m: &BigInt) -> Vec<Vec<BigInt>> {
+        let inverse = weights[0].modpow(&(m - 2), m);
+        let mut basis = vec![];
+        let mut first = vec![BigInt::zero(); weights.len()];
+        first[0] = m.clone();
+        basis.push(first);
+        for i in 1..weights.len() {
+            let mut row = vec![BigInt::zero(); weights.len()];
+            row[0] = (-&weights[i] * &inverse) % m;
+            row[i] = 1.into();
+            basis.push(row);
+        }
+        basis
+    }
+
+    fn assert_reduced(basis: &[Vec<BigInt>]) {
+        let integral = Integral::new(basis).unwrap();
+        let d = &integral.d;
+        for k in 1..basis.len() {
+            for j in 0..k {
+                assert!(integral.lambda[k][j].abs() * 2 <= d[j + 1]);
+            }
+            let lambda = &integral.lambda[k][k - 1];
+            assert!(
+                &d[k + 1] * &d[k - 1] * DELTA.1
+                    >= &d[k] * &d[k] * DELTA.0 - lambda * lambda * DELTA.1
+            );
+        }
+    }
+
+    #[test]
+    fn it_finds_a_planted_short_vector() {
+        let m = BigInt::from((1u64 << 61) - 1);
+        let planted = [3, -1, 4, -1, -5, 2]
+            .iter()
+            .map(|&x| BigInt::from(x))
+            .collect::<Vec<_>>();
+        let mut weights = [
+            0x0123_4567_89ab_cdefu64,
+            0x0fed_cba9_8765_4321,
+            0x1357_9bdf_2468_ace0,
+            0x0246_8ace_1357_9bdf,
+            0x1111_2222_3333_4444,
+            0x0aaa_bbbb_cccc_dddd,
+        ]
+        .iter()
+        .map(|&x| BigInt::from(x))
+        .collect::<Vec<_>>();
+        // fix the last weight so the planted vector is in the lattice
+        let partial = dot(&weights[..5], &planted[..5]);
+        weights[5] = (-partial * BigInt::from(2).modpow(&(&m - 2), &m)).mod_floor(&m);
+        let basis = kernel(&weights, &m);
+        let integral = Integral::new(&basis).unwrap();
+
+        let reduced = lll(&basis).unwrap();
+        assert_reduced(&reduced);
+        assert_eq!(Integral::new(&reduced).unwrap().d[6], integral.d[6]);
+        let negated = planted.iter().map(|x| -x).collect::<Vec<_>>();
+        assert!(reduced[0] == planted || reduced[0] == negated);
+
+        let stronger = bkz(&basis, 4).unwrap();
+        assert_reduced(&stronger);
+        assert!(dot(&stronger[0], &stronger[0]) <= dot(&reduced[0], &reduced[0]));
+        assert_eq!(Integral::new(&stronger).unwrap().d[6], integral.d[6]);
+    }
+
+    #[test]
+    fn it_rejects_bad_bases() {
+        let row = |x: &[i64]| x.iter().map(|&v| BigInt::from(v)).collect::<Vec<_>>();
+        assert_eq!(lll(&[]), Err(LcgError::InvalidBasis));
+        assert_eq!(
+            lll(&[row(&[1, 2]), row(&[2, 4])]),
+            Err(LcgError::InvalidBasis)
+        );
+        assert_eq!(lll(&[row(&[1, 2]), row(&[2])]), Err(LcgError::InvalidBasis));
+    }
+}
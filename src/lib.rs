@@ -14,13 +14,53 @@ unused_qualifications,
 warnings
 )]
 
-use itertools::izip;
-use num::Integer;
+mod analysis;
+mod builder;
+mod crack;
+mod error;
+mod indexed;
+mod int;
+mod lattice;
+mod lowbits;
+mod math;
+mod poly;
+mod pow2;
+mod reduction;
+mod residue;
+mod rewind;
+mod robust;
+mod stream;
+mod stride;
+mod sufficiency;
+mod truncated;
+
+pub use analysis::{Finding, LcgReport};
+pub use builder::{LCGBuilder, Param};
+pub use crack::{
+    crack_lcg, crack_lcg_all, crack_lcg_as, crack_lcg_ranked, crack_lcg_with, estimate_modulus,
+    Candidate, CrackResult, KnownParams, ModulusEstimate,
+};
+pub use error::LcgError;
+pub use indexed::crack_lcg_indexed;
+pub use int::LcgInt;
+pub use lattice::{bkz, lll, solve_bounded};
+pub use lowbits::{recover_low_bits, LowBitRecovery, LowBitSeeds};
+pub use pow2::{PowerOfTwoLCG, WrappingInt};
+pub use reduction::MontgomeryLCG;
+pub use residue::{recover_state_reduced, ReducedRecovery};
+pub use rewind::PredecessorTree;
+pub use robust::{crack_lcg_robust, RobustCrack};
+pub use stream::{CrackStatus, LcgCracker};
+pub use stride::{crack_lcg_strided, Strided};
+pub use sufficiency::{modulus_probability, spurious_modulus_probability, values_needed};
+pub use truncated::{crack_lcg_truncated, recover_state_truncated, recover_state_window};
+
+use num::{Integer, One, Signed, Zero};
 use num_bigint::{BigInt, ToBigInt};
 
 /// Rust's modulo operator is really remainder and not modular arithmetic so i have this
 fn modulo(a: &BigInt, m: &BigInt) -> BigInt {
-    ((a % m) + m) % m
+    a.mod_floor(m)
 }
 
 fn modinv(a: &BigInt, m: &BigInt) -> Option<BigInt> {
@@ -32,75 +72,58 @@ fn modinv(a: &BigInt, m: &BigInt) -> Option<BigInt> {
     }
 }
 
+/// Composes the affine map `x -> a*x + c` with itself `n` times and returns the resulting `(a, c)`
+///
+/// This is square-and-multiply over the map itself rather than the closed form `c*(a^n-1)/(a-1)` so a-1 never has to be invertible mod m
+fn affine_pow(a: &BigInt, c: &BigInt, n: &BigInt, m: &BigInt) -> (BigInt, BigInt) {
+    let compose = |(a1, c1): &(BigInt, BigInt), (a2, c2): &(BigInt, BigInt)| {
+        (modulo(&(a1 * a2), m), modulo(&(a1 * c2 + c1), m))
+    };
+    let mut result = (modulo(&num::one(), m), num::zero());
+    let mut base = (modulo(a, m), modulo(c, m));
+    for bit in 0..n.bits() {
+        if n.bit(bit) {
+            result = compose(&base, &result);
+        }
+        base = compose(&base, &base);
+    }
+    result
+}
+
 /// Represents a linear congruential generator which can calculate both forwards and backwards
-#[derive(Debug, Eq, PartialEq)]
-pub struct LCG {
+///
+/// Stored as [`BigInt`] by default, any other [`LcgInt`] such as `u32` or `u64` makes stepping much cheaper
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LCG<T = BigInt> {
     /// Seed
-    pub state: BigInt,
+    pub state: T,
     /// Multiplier
-    pub a: BigInt,
+    pub a: T,
     /// Increment
-    pub c: BigInt,
+    pub c: T,
     /// Modulus
-    pub m: BigInt,
+    pub m: T,
 }
 
-/// Tries to derive LCG parameters based on known values
-///
-/// This is probabilistic and may be wrong, especially for low number of values
-///
-/// [https://tailcall.net/blog/cracking-randomness-lcgs/](https://tailcall.net/blog/cracking-randomness-lcgs/)
-pub fn crack_lcg(values: &[isize]) -> Option<LCG> {
-    // not sure how this can be made generic across integral types
-    // main hangup is the primitive 0isize in the fold for the modulus
-    // because can't add isize and impl Integer + ops::Add
-    // searched around and didn't find anything so you need to pass variables in as isize until i can fix that
-    if values.len() < 3 {
-        return None;
-    }
-    let diffs = izip!(values, values.iter().skip(1))
-        .map(|(a, b)| b - a)
-        .collect::<Vec<isize>>();
-    let zeroes = izip!(&diffs, (&diffs).iter().skip(1), (&diffs).iter().skip(2))
-        .map(|(a, b, c)| c * a - b * b)
-        .collect::<Vec<_>>();
-    let modulus = zeroes
-        .iter()
-        .fold(0isize, |sum, val| sum.gcd(val))
-        .to_bigint()?;
-
-    let multiplier = modulo(
-        &((values[2] - values[1]).to_bigint()?
-            * modinv(
-                &(&values[1].to_bigint()? - &values[0].to_bigint()?),
-                &modulus,
-            )?),
-        &modulus,
-    );
-
-    let increment = modulo(&(values[1] - values[0] * &multiplier), &modulus);
-    Some(LCG {
-        state: values.last()?.to_bigint()?,
-        m: modulus,
-        a: multiplier,
-        c: increment,
-    })
-}
+impl<T: LcgInt> Iterator for LCG<T> {
+    type Item = T;
 
-impl Iterator for LCG {
-    type Item = BigInt;
+    fn next(&mut self) -> Option<T> {
+        Some(self.rand())
+    }
 
-    fn next(&mut self) -> Option<BigInt> {
+    fn nth(&mut self, n: usize) -> Option<T> {
+        self.skip(&n.to_bigint()?);
         Some(self.rand())
     }
 }
 
-impl LCG {
+impl<T: LcgInt> LCG<T> {
     /// Calculate the next value of the LCG
     ///
     /// `state * a + c % m`
-    pub fn rand(&mut self) -> BigInt {
-        self.state = modulo(&(&self.state * (&self.a) + (&self.c)), &self.m);
+    pub fn rand(&mut self) -> T {
+        self.state = T::mul_add_mod(&self.state, &self.a, &self.c, &self.m);
         self.state.clone()
     }
 
@@ -109,20 +132,213 @@ impl LCG {
     /// `modinv(a,m) * (state - c) % m`
     ///
     /// relies on modinv(a,m) existing (aka a and m must be coprime) and will return None otherwise
-    pub fn prev(&mut self) -> Option<BigInt> {
-        self.state = modulo(
-            &(modinv(&self.a, &self.m)? * (&self.state - (&self.c))),
-            &self.m,
-        );
+    pub fn prev(&mut self) -> Option<T> {
+        let m = self.m.to_big();
+        self.state = T::from_big(&modulo(
+            &(modinv(&self.a.to_big(), &m)? * (self.state.to_big() - self.c.to_big())),
+            &m,
+        ))?;
         Some(self.state.clone())
     }
+
+    /// Copy the LCG into another integer backend, returns None if a value doesn't fit
+    pub fn convert<U: LcgInt>(&self) -> Option<LCG<U>> {
+        Some(LCG {
+            state: U::from_big(&self.state.to_big())?,
+            a: U::from_big(&self.a.to_big())?,
+            c: U::from_big(&self.c.to_big())?,
+            m: U::from_big(&self.m.to_big())?,
+        })
+    }
+
+    /// Replace the state with `a*state + c (mod m)`
+    fn apply(&mut self, a: &BigInt, c: &BigInt, m: &BigInt) -> T {
+        let state = modulo(&(self.state.to_big() * a + c), m);
+        self.state =
+            T::from_big(&state).expect("anything reduced mod m fits in the same type as m");
+        self.state.clone()
+    }
+
+    /// Move the LCG by `n` steps in O(log n) multiplications and return the new state
+    ///
+    /// Positive `n` behaves like [`skip`](LCG::skip) and negative `n` rewinds like calling [`prev`](LCG::prev) `-n` times.
+    /// The inverse of the multiplier is only computed once no matter how far back the jump goes
+    ///
+    /// Negative jumps need modinv(a,m) to exist and will return [`LcgError::NonInvertibleMultiplier`] otherwise
+    pub fn jump(&mut self, n: &BigInt) -> Result<T, LcgError> {
+        if !n.is_negative() {
+            return Ok(LCG::skip(self, n));
+        }
+        // x = a^-1 * (y - c) is itself an affine map so rewinding is just skipping along the inverse
+        let m = self.m.to_big();
+        let a_inv =
+            modinv(&modulo(&self.a.to_big(), &m), &m).ok_or(LcgError::NonInvertibleMultiplier)?;
+        let c_inv = modulo(&-(&a_inv * self.c.to_big()), &m);
+        let (a, c) = affine_pow(&a_inv, &c_inv, &-n, &m);
+        Ok(self.apply(&a, &c, &m))
+    }
+
+    /// Advance the LCG by `n` steps in O(log n) multiplications and return the new state
+    ///
+    /// Equivalent to calling [`rand`](LCG::rand) `n` times and works for any modulus, including powers of two where a-1 is not invertible
+    ///
+    /// Method syntax on an owned `LCG` resolves to [`Iterator::skip`] so call it as `LCG::skip(&mut lcg, &n)` or through a `&mut LCG`
+    ///
+    /// Panics if `n` is negative
+    pub fn skip(&mut self, n: &BigInt) -> T {
+        assert!(!n.is_negative(), "cannot skip a negative number of steps");
+        let m = self.m.to_big();
+        let (a, c) = affine_pow(&self.a.to_big(), &self.c.to_big(), n, &m);
+        self.apply(&a, &c, &m)
+    }
+}
+
+impl LCG {
+    /// `x0` followed by every state the LCG steps through from it
+    fn states_from(&self, x0: &BigInt) -> impl Iterator<Item = BigInt> {
+        let lcg = LCG {
+            state: x0.clone(),
+            ..self.clone()
+        };
+        std::iter::once(x0.clone()).chain(lcg)
+    }
+
+    /// Count the calls to [`rand`](LCG::rand) needed before the state equals `target`
+    ///
+    /// Solves `a^n*state + c*(a^n-1)/(a-1) ≡ target (mod m)` for the smallest n >= 0 with Pohlig-Hellman and baby-step giant-step rather than stepping the generator.
+    /// Writing `u = (a-1)*x + c` turns the LCG into `u -> a*u (mod (a-1)*m)` so a-1 never has to be invertible.
+    ///
+    /// Returns None if the state never reaches `target`. Relies on factoring m and a-1 so it is only as fast as that is
+    pub fn distance_to(&self, target: &BigInt) -> Option<BigInt> {
+        let m = &self.m;
+        if target.is_negative() || target >= m {
+            return None;
+        }
+        let (state, a, c) = (
+            modulo(&self.state, m),
+            modulo(&self.a, m),
+            modulo(&self.c, m),
+        );
+        if state == *target {
+            return Some(BigInt::zero());
+        }
+        if a.is_zero() {
+            return if c == *target {
+                Some(BigInt::one())
+            } else {
+                None
+            };
+        }
+        if a.is_one() {
+            return Some(math::solve_linear(&c, &(target - &state), m)?.0);
+        }
+
+        let d: BigInt = &a - 1;
+        let modulus = &d * m;
+        let u0 = modulo(&(&d * &state + &c), &modulus);
+        let ut = modulo(&(&d * target + &c), &modulus);
+        // primes dividing a wipe out u after a few steps and only contribute a pre-period, the rest are periodic
+        let mut tail = 0u32;
+        let mut settles_on_target = true;
+        let (mut residue, mut period) = (BigInt::zero(), BigInt::one());
+        for (p, e) in math::factor_product(&[&d, m]) {
+            let pe = p.pow(e);
+            let vu = math::valuation(&u0, &p, e);
+            if (&a % &p).is_zero() {
+                let va = math::valuation(&a, &p, e);
+                tail = std::cmp::max(tail, (e - vu).div_ceil(va));
+                settles_on_target &= (&ut % &pe).is_zero();
+                continue;
+            }
+            if math::valuation(&ut, &p, e) != vu {
+                return None;
+            }
+            if vu == e {
+                continue;
+            }
+            let pk = p.pow(e - vu);
+            let goal = (&ut / p.pow(vu)) * math::inverse(&(&u0 / p.pow(vu)), &pk)?;
+            let (n, order) = math::discrete_log(&a, &modulo(&goal, &pk), &p, e - vu)?;
+            let (r, l) = math::crt(&residue, &period, &n, &order)?;
+            residue = r;
+            period = l;
+        }
+
+        // the first few steps still carry factors shared with a so just check them directly
+        let mut lcg = LCG {
+            state,
+            a,
+            c,
+            m: m.clone(),
+        };
+        for n in 1..tail {
+            if lcg.rand() == *target {
+                return Some(BigInt::from(n));
+            }
+        }
+        if !settles_on_target {
+            return None;
+        }
+        let tail = BigInt::from(tail);
+        if residue >= tail {
+            Some(residue)
+        } else {
+            Some(&residue + (&tail - &residue + &period - 1) / &period * &period)
+        }
+    }
+
+    /// Length of the cycle the LCG ends up in when started from the current state
+    ///
+    /// Computed from the multiplicative order of a modulo each prime power of m rather than by iterating, so it is exact even when the cycle is far too long to walk.
+    /// Relies on factoring m and a-1
+    pub fn period(&self) -> BigInt {
+        self.cycle().1
+    }
+
+    /// Number of calls to [`rand`](LCG::rand) before the state enters its cycle
+    ///
+    /// Always zero when a and m are coprime, otherwise the factors a shares with m are squeezed out of the state over the first few steps and those states never come back
+    pub fn tail_length(&self) -> BigInt {
+        self.cycle().0
+    }
+
+    /// `(tail_length, period)` using the same `u = (a-1)*x + c` substitution as [`distance_to`](LCG::distance_to)
+    fn cycle(&self) -> (BigInt, BigInt) {
+        let m = &self.m;
+        let (state, a, c) = (
+            modulo(&self.state, m),
+            modulo(&self.a, m),
+            modulo(&self.c, m),
+        );
+        if a.is_zero() {
+            let tail = if state == c { 0 } else { 1 };
+            return (BigInt::from(tail), BigInt::one());
+        }
+        if a.is_one() {
+            return (BigInt::zero(), m / c.gcd(m));
+        }
+
+        let d: BigInt = &a - 1;
+        let u0 = modulo(&(&d * &state + &c), &(&d * m));
+        let (mut tail, mut period) = (0u32, BigInt::one());
+        for (p, e) in math::factor_product(&[&d, m]) {
+            let vu = math::valuation(&u0, &p, e);
+            if (&a % &p).is_zero() {
+                let va = math::valuation(&a, &p, e);
+                tail = std::cmp::max(tail, (e - vu).div_ceil(va));
+            } else if vu < e {
+                period = period.lcm(&math::multiplicative_order(&a, &p, e - vu));
+            }
+        }
+        (BigInt::from(tail), period)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{crack_lcg, LCG};
+    use crate::{crack_lcg, crack_lcg_as, LcgError, LCG};
     use num::ToPrimitive;
-    use num_bigint::ToBigInt;
+    use num_bigint::{BigInt, ToBigInt};
 
     #[test]
     fn it_generates_numbers_correctly_forward_and_backwards() {
@@ -158,6 +374,216 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_skips_ahead_like_repeated_rand() {
+        // java.util.Random parameters, a-1 is not invertible mod 2^48
+        let mut stepped = LCG {
+            state: 0x1234_5678.to_bigint().unwrap(),
+            a: 0x5DEECE66Du64.to_bigint().unwrap(),
+            c: 11.to_bigint().unwrap(),
+            m: (1u64 << 48).to_bigint().unwrap(),
+        };
+        let mut skipped = LCG {
+            state: stepped.state.clone(),
+            a: stepped.a.clone(),
+            c: stepped.c.clone(),
+            m: stepped.m.clone(),
+        };
+
+        let expected = (&mut stepped).take(1000).last().unwrap();
+        assert_eq!(
+            LCG::skip(&mut skipped, &1000.to_bigint().unwrap()),
+            expected
+        );
+        assert_eq!(skipped, stepped);
+        assert_eq!(LCG::skip(&mut skipped, &0.to_bigint().unwrap()), expected);
+        assert_eq!(skipped.nth(4), stepped.nth(4));
+    }
+
+    #[test]
+    fn it_jumps_forwards_and_backwards() {
+        let mut rand = LCG {
+            state: 32760.to_bigint().unwrap(),
+            a: 5039.to_bigint().unwrap(),
+            c: 76581.to_bigint().unwrap(),
+            m: 479001599.to_bigint().unwrap(),
+        };
+
+        let forward = (&mut rand).take(10).collect::<Vec<_>>();
+        assert_eq!(
+            rand.jump(&(-9).to_bigint().unwrap()),
+            Ok(forward[0].clone())
+        );
+        assert_eq!(
+            rand.jump(&(-1).to_bigint().unwrap()),
+            Ok(32760.to_bigint().unwrap())
+        );
+        assert_eq!(rand.jump(&5.to_bigint().unwrap()), Ok(forward[4].clone()));
+
+        let far = 10.to_bigint().unwrap().pow(18);
+        rand.jump(&far).unwrap();
+        assert_eq!(rand.jump(&-far), Ok(forward[4].clone()));
+    }
+
+    #[test]
+    fn it_refuses_to_jump_backwards_without_inverse() {
+        let mut rand = LCG {
+            state: 7.to_bigint().unwrap(),
+            a: 6.to_bigint().unwrap(),
+            c: 1.to_bigint().unwrap(),
+            m: 16.to_bigint().unwrap(),
+        };
+        assert_eq!(
+            rand.jump(&(-1).to_bigint().unwrap()),
+            Err(LcgError::NonInvertibleMultiplier)
+        );
+        assert_eq!(rand.state, 7.to_bigint().unwrap());
+    }
+
+    #[test]
+    fn it_finds_distance_to_a_state() {
+        let rand = LCG {
+            state: 32760.to_bigint().unwrap(),
+            a: 5039.to_bigint().unwrap(),
+            c: 76581.to_bigint().unwrap(),
+            m: 479001599.to_bigint().unwrap(),
+        };
+        let mut target = LCG { ..rand.clone() };
+        let steps = 123_456_789.to_bigint().unwrap();
+        LCG::skip(&mut target, &steps);
+        assert_eq!(rand.distance_to(&target.state), Some(steps));
+        assert_eq!(rand.distance_to(&rand.state), Some(0.to_bigint().unwrap()));
+        assert_eq!(rand.distance_to(&479001599.to_bigint().unwrap()), None);
+
+        // power of two modulus where a-1 shares a factor with m
+        let java = LCG {
+            state: 0x1234_5678.to_bigint().unwrap(),
+            a: 0x5DEECE66Du64.to_bigint().unwrap(),
+            c: 11.to_bigint().unwrap(),
+            m: (1u64 << 48).to_bigint().unwrap(),
+        };
+        let mut target = LCG { ..java.clone() };
+        let steps = 10.to_bigint().unwrap().pow(13);
+        LCG::skip(&mut target, &steps);
+        assert_eq!(java.distance_to(&target.state), Some(steps));
+
+        // even multiplier, the odd part of the state dies out after a few steps
+        let degenerate = LCG {
+            state: 3.to_bigint().unwrap(),
+            a: 6.to_bigint().unwrap(),
+            c: 1.to_bigint().unwrap(),
+            m: 64.to_bigint().unwrap(),
+        };
+        let outputs = LCG {
+            ..degenerate.clone()
+        }
+        .take(20)
+        .collect::<Vec<_>>();
+        for (n, value) in outputs.iter().enumerate() {
+            let first = outputs.iter().position(|x| x == value).unwrap() + 1;
+            assert_eq!(
+                degenerate.distance_to(value),
+                Some(first.to_bigint().unwrap()),
+                "{}",
+                n
+            );
+        }
+        assert_eq!(degenerate.distance_to(&2.to_bigint().unwrap()), None);
+    }
+
+    #[test]
+    fn it_computes_period_and_tail_like_brute_force() {
+        for &m in &[64, 97, 360] {
+            for a in 0..m {
+                for &(c, state) in &[(0, 1), (3, 5), (m / 2, 7)] {
+                    let rand = LCG {
+                        state: (state % m).to_bigint().unwrap(),
+                        a: a.to_bigint().unwrap(),
+                        c: c.to_bigint().unwrap(),
+                        m: m.to_bigint().unwrap(),
+                    };
+                    let mut seen = std::collections::HashMap::new();
+                    let mut walker = rand.clone();
+                    seen.insert(walker.state.clone(), 0);
+                    let (tail, period) = (1..)
+                        .find_map(|n| {
+                            let first = *seen.entry(walker.rand()).or_insert(n);
+                            if first == n {
+                                None
+                            } else {
+                                Some((first, n - first))
+                            }
+                        })
+                        .unwrap();
+                    assert_eq!(rand.tail_length(), tail.to_bigint().unwrap(), "{:?}", rand);
+                    assert_eq!(rand.period(), period.to_bigint().unwrap(), "{:?}", rand);
+                }
+            }
+        }
+
+        let java = LCG {
+            state: 0.to_bigint().unwrap(),
+            a: 0x5DEECE66Du64.to_bigint().unwrap(),
+            c: 11.to_bigint().unwrap(),
+            m: (1u64 << 48).to_bigint().unwrap(),
+        };
+        assert_eq!(java.period(), java.m);
+    }
+
+    #[test]
+    fn it_generates_the_same_numbers_with_every_backend() {
+        let big = LCG {
+            state: 32760.to_bigint().unwrap(),
+            a: 5039.to_bigint().unwrap(),
+            c: 76581.to_bigint().unwrap(),
+            m: 479001599.to_bigint().unwrap(),
+        };
+        let expected = big.clone().take(100).collect::<Vec<_>>();
+        let widen = |values: Vec<u128>| {
+            values
+                .into_iter()
+                .map(|x| x.to_bigint().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let narrow = big.convert::<u32>().unwrap();
+        assert_eq!(widen(narrow.take(100).map(u128::from).collect()), expected);
+        let mut narrow = big.convert::<u64>().unwrap();
+        assert_eq!(
+            widen((&mut narrow).take(100).map(u128::from).collect()),
+            expected
+        );
+        assert_eq!(narrow.prev(), expected[98].to_u64());
+        assert_eq!(
+            narrow.jump(&(-98).to_bigint().unwrap()),
+            Ok(expected[0].to_u64().unwrap())
+        );
+
+        // a 127 bit modulus has to go through double-and-add instead of a wider multiply
+        let m = (1u128 << 127) - 1;
+        let mut wide = LCG {
+            state: 1u128 << 100,
+            a: m - 12345,
+            c: m - 1,
+            m,
+        };
+        let mut big = wide.convert::<BigInt>().unwrap();
+        assert_eq!(
+            widen((&mut wide).take(100).collect()),
+            (&mut big).take(100).collect::<Vec<_>>()
+        );
+        assert_eq!(big.convert::<u64>(), None);
+
+        let cracked = crack_lcg_as::<u32>(
+            &expected
+                .iter()
+                .map(|x| x.to_isize().unwrap())
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+        assert_eq!(cracked.m, 479001599);
+    }
+
     #[test]
     fn it_cracks_lcg_correctly() {
         let mut rand = LCG {
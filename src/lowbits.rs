@@ -0,0 +1,216 @@
+//! Power of two LCGs leaking their low bits, see [`recover_low_bits`]
+//!
+//! Carries only go up, so bit i of every state only depends on bits 0 to i of the one before and the lowest j bits are an LCG mod 2^j on their own
+
+use crate::crack::MAX_CANDIDATES;
+use crate::{PowerOfTwoLCG, WrappingInt};
+use num_bigint::BigInt;
+
+/// Just bit `i`
+fn bit<T: WrappingInt>(i: u32) -> T {
+    T::wrapping_sub(T::low_mask(i + 1), T::low_mask(i))
+}
+
+/// Whether the state `seed` followed by `lcg` leaks `leaks` through `mask`, only looking at the lowest `bits` bits
+fn leaks_match<T: WrappingInt>(
+    lcg: &PowerOfTwoLCG<T>,
+    seed: T,
+    mask: T,
+    leaks: &[T],
+    bits: u32,
+) -> bool {
+    let mut lcg = PowerOfTwoLCG {
+        state: seed,
+        bits,
+        ..*lcg
+    };
+    let mask = T::and(mask, lcg.mask());
+    leaks.iter().enumerate().all(|(i, &leak)| {
+        if i > 0 {
+            lcg.rand();
+        }
+        T::and(lcg.state, mask) == T::and(leak, mask)
+    })
+}
+
+/// What [`recover_low_bits`] pinned down
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LowBitRecovery<T = u64> {
+    lcg: PowerOfTwoLCG<T>,
+    mask: T,
+    leaks: Vec<T>,
+    /// How many of the lowest bits of the first state were lifted from the leaks
+    pub known_bits: u32,
+    /// Every value the lowest `known_bits` bits of the first state can take given the leaks, usually just one
+    pub candidates: Vec<T>,
+}
+
+impl<T: WrappingInt> LowBitRecovery<T> {
+    /// Bits of the first state the leaks don't pin down, everything above `known_bits` and any bit below where the candidates disagree
+    pub fn undetermined(&self) -> T {
+        let mut undetermined = T::wrapping_sub(self.lcg.mask(), T::low_mask(self.known_bits));
+        for i in 0..self.known_bits {
+            let first = T::and(self.candidates[0], bit(i));
+            if self.candidates.iter().any(|&x| T::and(x, bit(i)) != first) {
+                undetermined = T::wrapping_add(undetermined, bit(i));
+            }
+        }
+        undetermined
+    }
+
+    /// How many full states [`seeds`](LowBitRecovery::seeds) goes through before filtering them against the leaks
+    pub fn search_space(&self) -> BigInt {
+        BigInt::from(self.candidates.len()) << (self.lcg.bits - self.known_bits) as usize
+    }
+
+    /// Brute force stage, every full first state consistent with the leaks as an LCG with that state
+    ///
+    /// Goes through [`search_space`](LowBitRecovery::search_space) states, so check it first and narrow the result down with something else that's known,
+    /// like a full output or a hash of the seed
+    pub fn seeds(&self) -> LowBitSeeds<'_, T> {
+        LowBitSeeds {
+            recovery: self,
+            candidate: 0,
+            high: T::ZERO,
+        }
+    }
+}
+
+/// Iterator returned by [`LowBitRecovery::seeds`]
+#[derive(Debug, Clone)]
+pub struct LowBitSeeds<'a, T> {
+    recovery: &'a LowBitRecovery<T>,
+    candidate: usize,
+    /// Bits above `known_bits` to try next
+    high: T,
+}
+
+impl<T: WrappingInt> Iterator for LowBitSeeds<'_, T> {
+    type Item = PowerOfTwoLCG<T>;
+
+    fn next(&mut self) -> Option<PowerOfTwoLCG<T>> {
+        let recovery = self.recovery;
+        let lcg = &recovery.lcg;
+        while let Some(&low) = recovery.candidates.get(self.candidate) {
+            let seed = T::wrapping_add(low, self.high);
+            if recovery.known_bits == lcg.bits {
+                self.candidate += 1;
+            } else {
+                self.high = T::and(
+                    T::wrapping_add(self.high, bit(recovery.known_bits)),
+                    lcg.mask(),
+                );
+                if self.high == T::ZERO {
+                    self.candidate += 1;
+                }
+            }
+            if leaks_match(lcg, seed, recovery.mask, &recovery.leaks, lcg.bits) {
+                return Some(PowerOfTwoLCG {
+                    state: seed,
+                    ..*lcg
+                });
+            }
+        }
+        None
+    }
+}
+
+/// Lift the low bits of a known power of two LCG's state from consecutive `state & mask` leaks, such as `state % 2^j`
+///
+/// Goes up one bit at a time keeping every value of the first state's low bits that reproduces the leaks so far, a leaked bit in any later state
+/// also pins down the lower bits that carry into it. Stops at the highest leaked bit, or once more than 65536 values are left.
+/// The state of `lcg` is ignored, and None means no state produces these leaks
+pub fn recover_low_bits<T: WrappingInt>(
+    lcg: &PowerOfTwoLCG<T>,
+    mask: T,
+    leaks: &[T],
+) -> Option<LowBitRecovery<T>> {
+    if leaks.is_empty() || lcg.bits == 0 || lcg.bits > T::BITS {
+        return None;
+    }
+    let mask = T::and(mask, lcg.mask());
+    let top = (0..lcg.bits)
+        .rev()
+        .find(|&i| T::and(mask, bit(i)) != T::ZERO);
+    let mut candidates = vec![T::ZERO];
+    let mut known_bits = 0;
+    for i in 0..top.map_or(0, |top| top + 1) {
+        let next = candidates
+            .iter()
+            .flat_map(|&x| vec![x, T::wrapping_add(x, bit(i))])
+            .filter(|&x| leaks_match(lcg, x, mask, leaks, i + 1))
+            .collect::<Vec<_>>();
+        if next.is_empty() {
+            return None;
+        }
+        if next.len() > MAX_CANDIDATES {
+            break;
+        }
+        candidates = next;
+        known_bits = i + 1;
+    }
+    Some(LowBitRecovery {
+        lcg: PowerOfTwoLCG {
+            state: T::ZERO,
+            ..*lcg
+        },
+        mask,
+        leaks: leaks.iter().map(|&leak| T::and(leak, mask)).collect(),
+        known_bits,
+        candidates,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{recover_low_bits, PowerOfTwoLCG};
+    use num_bigint::BigInt;
+
+    #[test]
+    fn it_lifts_the_low_bits() {
+        let mut rand = PowerOfTwoLCG {
+            state: 0x1234_5678u32,
+            a: 1103515245,
+            c: 12345,
+            bits: 31,
+        };
+        let states = (&mut rand).take(40).collect::<Vec<_>>();
+        let leaks = states.iter().map(|x| x % (1 << 20)).collect::<Vec<_>>();
+        let recovery = recover_low_bits(&rand, (1 << 20) - 1, &leaks).unwrap();
+        assert_eq!(recovery.known_bits, 20);
+        assert_eq!(recovery.candidates, vec![states[0] % (1 << 20)]);
+        assert_eq!(recovery.undetermined(), 0x7FF0_0000);
+        assert_eq!(recovery.search_space(), BigInt::from(1 << 11));
+        // a full output later on is enough to pick the seed out
+        let found = recovery
+            .seeds()
+            .filter(|lcg| lcg.clone().nth(5) == Some(states[6]))
+            .collect::<Vec<_>>();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].state, states[0]);
+        assert_eq!(recovery.seeds().count(), 1 << 11);
+    }
+
+    #[test]
+    fn it_lifts_through_gaps_in_the_mask() {
+        let mut rand = PowerOfTwoLCG {
+            state: 0x00C0_FFEEu64,
+            a: 0x5DEECE66D,
+            c: 11,
+            bits: 48,
+        };
+        let states = (&mut rand).take(64).collect::<Vec<_>>();
+        // only bits 0 and 12 leak, bits 1 to 11 carry into bit 12 of the next states
+        let mask = 0x1001;
+        let leaks = states.iter().map(|x| x & mask).collect::<Vec<_>>();
+        let recovery = recover_low_bits(&rand, mask, &leaks).unwrap();
+        assert_eq!(recovery.known_bits, 13);
+        assert!(recovery.candidates.contains(&(states[0] & 0x1FFF)));
+        assert!(recovery.search_space() < BigInt::from(1u64 << 48));
+        assert_eq!(recovery.undetermined() & !0x1FFF, (1 << 48) - (1 << 13));
+
+        let mut wrong = leaks.clone();
+        wrong[10] ^= 1;
+        assert_eq!(recover_low_bits(&rand, mask, &wrong), None);
+    }
+}
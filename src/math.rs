@@ -0,0 +1,467 @@
+//! Number theory helpers used by the jump-ahead, distance and period code
+//!
+//! Everything in here expects positive moduli and will happily return nonsense otherwise
+
+use crate::{modinv, modulo};
+use num::{Integer, One, Signed, ToPrimitive, Zero};
+use num_bigint::BigInt;
+use std::collections::{BTreeMap, HashMap};
+
+/// Anything below this is found by trial division before Pollard's rho is started
+const TRIAL_DIVISION_BOUND: usize = 1 << 12;
+
+/// Roots mod powers of primes below this are lifted one base-p digit at a time instead of going through discrete logs
+const ROOT_LIFTING_BOUND: u32 = 1 << 12;
+
+/// Bases for Miller-Rabin, deterministic for n < 3.3 * 10^24 and good enough beyond that
+const WITNESSES: [u32; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+
+/// Sieve of Eratosthenes for every prime below `bound`
+pub(crate) fn small_primes(bound: usize) -> Vec<u32> {
+    let mut sieve = vec![true; bound];
+    let mut primes = vec![];
+    for i in 2..bound {
+        if sieve[i] {
+            primes.push(i as u32);
+            (i * i..bound).step_by(i).for_each(|j| sieve[j] = false);
+        }
+    }
+    primes
+}
+
+/// `x / y` as a float without overflowing for huge values
+pub(crate) fn ratio(x: &BigInt, y: &BigInt) -> f64 {
+    let shift = (x.bits().max(y.bits()) as usize).saturating_sub(64);
+    let (x, y) = ((x >> shift).to_f64(), (y >> shift).to_f64());
+    x.unwrap_or(0.0) / y.unwrap_or(1.0)
+}
+
+/// modinv which doesn't care whether `a` has been reduced mod m yet
+pub(crate) fn inverse(a: &BigInt, m: &BigInt) -> Option<BigInt> {
+    modinv(&modulo(a, m), m)
+}
+
+/// Miller-Rabin primality test
+pub(crate) fn is_probable_prime(n: &BigInt) -> bool {
+    if *n < BigInt::from(2) {
+        return false;
+    }
+    for &p in WITNESSES.iter() {
+        if *n == BigInt::from(p) {
+            return true;
+        }
+        if (n % p).is_zero() {
+            return false;
+        }
+    }
+    let n_minus_one: BigInt = n - 1;
+    let s = n_minus_one.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_one >> s as usize;
+    'witness: for &w in WITNESSES.iter() {
+        let mut x = BigInt::from(w).modpow(&d, n);
+        if x.is_one() || x == n_minus_one {
+            continue;
+        }
+        for _ in 1..s {
+            x = &x * &x % n;
+            if x == n_minus_one {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// Brent's variant of Pollard's rho, returns a non-trivial factor of the composite `n`
+fn pollard_rho(n: &BigInt) -> BigInt {
+    if n.is_even() {
+        return BigInt::from(2);
+    }
+    for increment in 1u32.. {
+        let f = |x: &BigInt| (x * x + increment) % n;
+        let (mut x, mut y, mut ys) = (BigInt::from(2), BigInt::from(2), BigInt::from(2));
+        let (mut g, mut q, mut r) = (BigInt::one(), BigInt::one(), 1u64);
+        while g.is_one() {
+            x = y.clone();
+            for _ in 0..r {
+                y = f(&y);
+            }
+            let mut k = 0;
+            while k < r && g.is_one() {
+                ys = y.clone();
+                for _ in 0..std::cmp::min(128, r - k) {
+                    y = f(&y);
+                    q = q * (&x - &y).abs() % n;
+                }
+                g = q.gcd(n);
+                k += 128;
+            }
+            r *= 2;
+        }
+        if g == *n {
+            // the batched gcd overshot, redo the last batch one step at a time
+            loop {
+                ys = f(&ys);
+                g = (&x - &ys).abs().gcd(n);
+                if !g.is_one() {
+                    break;
+                }
+            }
+        }
+        if g != *n {
+            return g;
+        }
+    }
+    unreachable!()
+}
+
+/// Splits `n` into the sorted `(prime, exponent)` pairs of its primes below `bound` and whatever is left over
+pub(crate) fn trial_factor(n: &BigInt, bound: usize) -> (Vec<(BigInt, u32)>, BigInt) {
+    let mut factors = vec![];
+    let mut n = n.abs();
+    if n.is_zero() {
+        return (factors, n);
+    }
+    for p in small_primes(bound) {
+        if BigInt::from(p) * p > n {
+            // whatever is left is 1 or a prime
+            if n > BigInt::one() && n < BigInt::from(bound) {
+                factors.push((std::mem::replace(&mut n, BigInt::one()), 1));
+            }
+            break;
+        }
+        let mut e = 0;
+        while (&n % p).is_zero() {
+            n /= p;
+            e += 1;
+        }
+        if e > 0 {
+            factors.push((BigInt::from(p), e));
+        }
+    }
+    (factors, n)
+}
+
+/// Factors `n` into sorted `(prime, exponent)` pairs
+pub(crate) fn factor(n: &BigInt) -> Vec<(BigInt, u32)> {
+    if n.is_zero() {
+        return vec![];
+    }
+    let (small, n) = trial_factor(n, TRIAL_DIVISION_BOUND);
+    let mut factors = small.into_iter().collect::<BTreeMap<_, _>>();
+    let mut composites = vec![n];
+    while let Some(n) = composites.pop() {
+        if n.is_one() {
+            continue;
+        }
+        if is_probable_prime(&n) {
+            *factors.entry(n).or_insert(0) += 1;
+        } else {
+            let d = pollard_rho(&n);
+            composites.push(&n / &d);
+            composites.push(d);
+        }
+    }
+    factors.into_iter().collect()
+}
+
+/// Factors a product without multiplying it out first, which keeps each number handed to Pollard's rho small
+pub(crate) fn factor_product(values: &[&BigInt]) -> Vec<(BigInt, u32)> {
+    let mut factors = BTreeMap::new();
+    for (p, e) in values.iter().flat_map(|v| factor(v)) {
+        *factors.entry(p).or_insert(0) += e;
+    }
+    factors.into_iter().collect()
+}
+
+/// Largest k <= cap such that p^k divides x, zero counts as divisible by everything
+pub(crate) fn valuation(x: &BigInt, p: &BigInt, cap: u32) -> u32 {
+    let mut x = x.clone();
+    let mut k = 0;
+    while k < cap && (&x % p).is_zero() {
+        x /= p;
+        k += 1;
+    }
+    k
+}
+
+/// Multiplicative order of the unit `a` mod p^e
+pub(crate) fn multiplicative_order(a: &BigInt, p: &BigInt, e: u32) -> BigInt {
+    let modulus = p.pow(e);
+    if modulus.is_one() {
+        return BigInt::one();
+    }
+    let mut group = factor(&(p - 1));
+    if e > 1 {
+        group.push((p.clone(), e - 1));
+    }
+    let mut order = p.pow(e - 1) * (p - 1);
+    for (q, k) in group {
+        for _ in 0..k {
+            let candidate = &order / &q;
+            if !a.modpow(&candidate, &modulus).is_one() {
+                break;
+            }
+            order = candidate;
+        }
+    }
+    order
+}
+
+/// Solves `r ≡ r1 (mod m1)` and `r ≡ r2 (mod m2)` for moduli which need not be coprime
+///
+/// Returns the solution in [0, lcm(m1, m2)) and the lcm itself
+pub(crate) fn crt(r1: &BigInt, m1: &BigInt, r2: &BigInt, m2: &BigInt) -> Option<(BigInt, BigInt)> {
+    let g = m1.gcd(m2);
+    let diff = r2 - r1;
+    if !(&diff % &g).is_zero() {
+        return None;
+    }
+    let lcm = m1 / &g * m2;
+    let step = inverse(&(m1 / &g), &(m2 / &g))? * (diff / &g);
+    Some((modulo(&(r1 + m1 * modulo(&step, &(m2 / &g))), &lcm), lcm))
+}
+
+/// Solves `a*x ≡ b (mod m)`
+///
+/// Returns the smallest solution and the spacing between solutions, so the full set is `x + k*step` for `0 <= k < m/step`
+pub(crate) fn solve_linear(a: &BigInt, b: &BigInt, m: &BigInt) -> Option<(BigInt, BigInt)> {
+    let g = modulo(a, m).gcd(m);
+    if !modulo(b, &g).is_zero() {
+        return None;
+    }
+    let step = m / &g;
+    let x = inverse(&(a / &g), &step)? * (b / &g);
+    Some((modulo(&x, &step), step))
+}
+
+/// Finds x in [0, order) with g^x ≡ h (mod modulus), where `order` is the order of g
+fn baby_step_giant_step(
+    g: &BigInt,
+    h: &BigInt,
+    order: &BigInt,
+    modulus: &BigInt,
+) -> Option<BigInt> {
+    let steps = order.sqrt() + 1u32;
+    let mut table = HashMap::new();
+    let mut baby = modulo(&BigInt::one(), modulus);
+    for j in 0..steps.to_u64()? {
+        table.entry(baby.clone()).or_insert(j);
+        baby = baby * g % modulus;
+    }
+    let giant = inverse(&g.modpow(&steps, modulus), modulus)?;
+    let mut gamma = modulo(h, modulus);
+    for i in 0..steps.to_u64()? {
+        if let Some(j) = table.get(&gamma) {
+            return Some(BigInt::from(i) * &steps + j);
+        }
+        gamma = gamma * &giant % modulus;
+    }
+    None
+}
+
+/// Pohlig-Hellman discrete log for the unit `base` mod p^e
+///
+/// Returns the smallest n >= 0 with base^n ≡ target along with the order of base, every other solution differs from n by a multiple of that order
+pub(crate) fn discrete_log(
+    base: &BigInt,
+    target: &BigInt,
+    p: &BigInt,
+    e: u32,
+) -> Option<(BigInt, BigInt)> {
+    let modulus = p.pow(e);
+    let order = multiplicative_order(base, p, e);
+    let residue = pohlig_hellman(base, target, &order, &factor(&order), &modulus)?;
+    if base.modpow(&residue, &modulus) == modulo(target, &modulus) {
+        Some((residue, order))
+    } else {
+        None
+    }
+}
+
+/// n mod `order` with base^n ≡ target, for `base` of the given order whose factors are `factors`
+///
+/// Takes a baby-step giant-step of size √q per digit for every prime q dividing the order, and doesn't check that target is a power of base
+fn pohlig_hellman(
+    base: &BigInt,
+    target: &BigInt,
+    order: &BigInt,
+    factors: &[(BigInt, u32)],
+    modulus: &BigInt,
+) -> Option<BigInt> {
+    let (mut residue, mut combined) = (BigInt::zero(), BigInt::one());
+    for (q, k) in factors {
+        // solve for n mod q^k one base-q digit at a time, each digit lives in a subgroup of order q
+        let k = *k;
+        let qk = q.pow(k);
+        let g = base.modpow(&(order / &qk), modulus);
+        let h = target.modpow(&(order / &qk), modulus);
+        let gamma = g.modpow(&q.pow(k - 1), modulus);
+        let g_inv = inverse(&g, modulus)?;
+        let mut x = BigInt::zero();
+        for i in 0..k {
+            let hi = (g_inv.modpow(&x, modulus) * &h).modpow(&q.pow(k - 1 - i), modulus);
+            x += baby_step_giant_step(&gamma, &hi, q, modulus)? * q.pow(i);
+        }
+        let (r, l) = crt(&residue, &combined, &x, &qk)?;
+        residue = r;
+        combined = l;
+    }
+    Some(residue)
+}
+
+/// Every x mod p^e with x^k ≡ target, or None if there are more than `cap`
+///
+/// Small primes lift roots mod p^j to p^(j+1) by trying every next digit, which also covers targets sharing a factor with p.
+/// Large primes need target to be a unit and use `target^(k^-1)` when k is invertible mod φ(p^e). Otherwise the units split into the part whose order
+/// only has primes dividing k and the rest, like Adleman-Manders-Miller, and only the first takes a discrete log, which stays cheap for any k that's easy to factor
+pub(crate) fn kth_roots(
+    k: u64,
+    target: &BigInt,
+    p: &BigInt,
+    e: u32,
+    cap: usize,
+) -> Option<Vec<BigInt>> {
+    let k = BigInt::from(k);
+    let modulus = p.pow(e);
+    let target = modulo(target, &modulus);
+    if *p < BigInt::from(ROOT_LIFTING_BOUND) {
+        let mut roots = vec![BigInt::zero()];
+        let mut power = BigInt::one();
+        for _ in 0..e {
+            let next = &power * p;
+            let wanted = modulo(&target, &next);
+            let mut lifted = vec![];
+            for r in &roots {
+                for i in 0..p.to_u32().unwrap_or(0) {
+                    let x = r + &power * i;
+                    if x.modpow(&k, &next) == wanted {
+                        lifted.push(x);
+                    }
+                }
+            }
+            roots = lifted;
+            if roots.len() > cap {
+                return None;
+            }
+            power = next;
+        }
+        return Some(roots);
+    }
+    if (&target % p).is_zero() {
+        return if e == 1 {
+            Some(vec![BigInt::zero()])
+        } else {
+            None
+        };
+    }
+    let phi = p.pow(e - 1) * (p - 1);
+    if k.gcd(&phi).is_one() {
+        return Some(vec![target.modpow(&inverse(&k, &phi)?, &modulus)]);
+    }
+    let shared = factor(&k)
+        .into_iter()
+        .filter(|(q, _)| (&phi % q).is_zero())
+        .map(|(q, _)| {
+            let v = valuation(&phi, &q, u32::MAX);
+            (q, v)
+        })
+        .collect::<Vec<_>>();
+    let sylow = shared
+        .iter()
+        .fold(BigInt::one(), |product, (q, v)| product * q.pow(*v));
+    let rest = &phi / &sylow;
+    // target = t1 * t2 with t1 in the subgroup of order sylow, t2 in the one of order rest where k is invertible
+    let split = modulo(&(&rest * inverse(&rest, &sylow)?), &phi);
+    let t1 = target.modpow(&split, &modulus);
+    let x2 = if rest.is_one() {
+        BigInt::one()
+    } else {
+        let t2 = target.modpow(&modulo(&(BigInt::one() - &split), &phi), &modulus);
+        t2.modpow(&inverse(&k, &rest)?, &modulus)
+    };
+    let generator = (2u32..)
+        .map(|x| BigInt::from(x).modpow(&rest, &modulus))
+        .find(|h| {
+            shared
+                .iter()
+                .all(|(q, _)| !h.modpow(&(&sylow / q), &modulus).is_one())
+        })?;
+    let log = pohlig_hellman(&generator, &t1, &sylow, &shared, &modulus)?;
+    let (first, step) = match solve_linear(&k, &log, &sylow) {
+        Some(solution) => solution,
+        None => return Some(vec![]),
+    };
+    let count = (&sylow / &step).to_usize().filter(|&x| x <= cap)?;
+    Some(
+        (0..count)
+            .map(|i| generator.modpow(&(&first + &step * i), &modulus) * &x2 % &modulus)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::{discrete_log, factor, kth_roots, solve_linear};
+    use num_bigint::{BigInt, ToBigInt};
+
+    #[test]
+    fn it_factors_products_of_large_primes() {
+        let (p, q) = (
+            BigInt::from(4294967291u64),
+            BigInt::from(2305843009213693951u64),
+        );
+        let n = &p * &p * &q * 12;
+        assert_eq!(
+            factor(&n),
+            vec![
+                (2.to_bigint().unwrap(), 2),
+                (3.to_bigint().unwrap(), 1),
+                (p, 2),
+                (q, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn it_solves_discrete_logs_and_linear_congruences() {
+        let p = BigInt::from(479001599);
+        let base = BigInt::from(5039);
+        let target = base.modpow(&BigInt::from(123456789), &p);
+        let (n, order) = discrete_log(&base, &target, &p, 1).unwrap();
+        assert_eq!(base.modpow(&n, &p), target);
+        assert!(n < order);
+
+        let (x, step) = solve_linear(&6.into(), &4.into(), &16.into()).unwrap();
+        assert_eq!((x, step), (6.to_bigint().unwrap(), 8.to_bigint().unwrap()));
+        assert_eq!(solve_linear(&6.into(), &3.into(), &16.into()), None);
+    }
+
+    #[test]
+    fn it_takes_roots_without_a_full_discrete_log() {
+        // (p-1)/2 is prime for both so a discrete log over all of p-1 would need a 2^25 or 2^31 table
+        for p in &[2251799813687339u64, 18446744073709550147] {
+            let p = BigInt::from(*p);
+            let square = BigInt::from(0x1234_5678_9abcu64).modpow(&2.into(), &p);
+            let mut roots = kth_roots(2, &square, &p, 1, 16).unwrap();
+            roots.sort();
+            assert_eq!(roots.len(), 2);
+            assert!(roots.contains(&BigInt::from(0x1234_5678_9abcu64)));
+            assert_eq!(&roots[0] + &roots[1], p);
+            // both are 3 mod 8 so 2 is a non-residue
+            assert_eq!(kth_roots(2, &2.into(), &p, 1, 16), Some(vec![]));
+        }
+
+        // 2^32 + 1 = 641 * 6700417 and 6700417 - 1 = 2^7 * 3 * 17449, so there are 6 sixth roots mod its square
+        let p = BigInt::from(6700417);
+        let modulus = p.pow(2);
+        let target = BigInt::from(123457).modpow(&6.into(), &modulus);
+        let roots = kth_roots(6, &target, &p, 2, 16).unwrap();
+        assert_eq!(roots.len(), 6);
+        assert!(roots.contains(&BigInt::from(123457)));
+        assert!(roots
+            .iter()
+            .all(|r| r.modpow(&6.into(), &modulus) == target));
+    }
+}
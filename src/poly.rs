@@ -0,0 +1,126 @@
+//! Integer polynomials for eliminating unknowns from LCG relations
+//!
+//! Coefficients are stored lowest degree first without trailing zeroes
+
+use crate::{math, modulo};
+use num::{One, Zero};
+use num_bigint::BigInt;
+
+/// Drop leading zero coefficients
+pub(crate) fn trim(mut p: Vec<BigInt>) -> Vec<BigInt> {
+    while p.last().is_some_and(Zero::is_zero) {
+        p.pop();
+    }
+    p
+}
+
+/// Determinant by fraction free Gaussian elimination, every division is exact
+fn bareiss(mut matrix: Vec<Vec<BigInt>>) -> BigInt {
+    let n = matrix.len();
+    let mut sign = BigInt::one();
+    let mut previous = BigInt::one();
+    for k in 0..n {
+        let pivot = match (k..n).find(|&i| !matrix[i][k].is_zero()) {
+            Some(pivot) => pivot,
+            None => return BigInt::zero(),
+        };
+        if pivot != k {
+            matrix.swap(pivot, k);
+            sign = -sign;
+        }
+        for i in k + 1..n {
+            for j in k + 1..n {
+                let value = &matrix[i][j] * &matrix[k][k] - &matrix[i][k] * &matrix[k][j];
+                matrix[i][j] = value / &previous;
+            }
+        }
+        previous = matrix[k][k].clone();
+    }
+    sign * previous
+}
+
+/// Resultant of two polynomials, zero exactly when they share a factor over the rationals
+pub(crate) fn resultant(p: &[BigInt], q: &[BigInt]) -> BigInt {
+    if p.is_empty() || q.is_empty() {
+        return BigInt::zero();
+    }
+    let (dp, dq) = (p.len() - 1, q.len() - 1);
+    let n = dp + dq;
+    if n == 0 {
+        return BigInt::one();
+    }
+    // Sylvester matrix, highest degree first
+    let mut matrix = vec![vec![BigInt::zero(); n]; n];
+    for i in 0..dq {
+        for (j, x) in p.iter().rev().enumerate() {
+            matrix[i][i + j] = x.clone();
+        }
+    }
+    for i in 0..dp {
+        for (j, x) in q.iter().rev().enumerate() {
+            matrix[dq + i][i + j] = x.clone();
+        }
+    }
+    bareiss(matrix)
+}
+
+/// Remainder of `p` divided by `q` mod m, None if the leading coefficient of q isn't invertible
+fn rem_mod(p: &[BigInt], q: &[BigInt], m: &BigInt) -> Option<Vec<BigInt>> {
+    let mut p = p.to_vec();
+    let lead = math::inverse(q.last()?, m)?;
+    while p.len() >= q.len() {
+        let factor = modulo(&(p.last()? * &lead), m);
+        let shift = p.len() - q.len();
+        for (i, x) in q.iter().enumerate() {
+            p[shift + i] = modulo(&(&p[shift + i] - &factor * x), m);
+        }
+        p = trim(p);
+    }
+    Some(p)
+}
+
+/// `p(x) mod m` by Horner's rule
+pub(crate) fn eval_mod(p: &[BigInt], x: &BigInt, m: &BigInt) -> BigInt {
+    p.iter().rev().fold(BigInt::zero(), |acc, coefficient| {
+        modulo(&(acc * x + coefficient), m)
+    })
+}
+
+/// Monic gcd of two polynomials mod m
+///
+/// Treats m as if it were prime, returns None when that breaks down because a leading coefficient shares a factor with m
+pub(crate) fn gcd_mod(p: &[BigInt], q: &[BigInt], m: &BigInt) -> Option<Vec<BigInt>> {
+    let reduce = |p: &[BigInt]| trim(p.iter().map(|x| modulo(x, m)).collect());
+    let (mut p, mut q) = (reduce(p), reduce(q));
+    while !q.is_empty() {
+        let r = rem_mod(&p, &q, m)?;
+        p = q;
+        q = r;
+    }
+    let lead = math::inverse(p.last()?, m)?;
+    Some(p.iter().map(|x| modulo(&(x * &lead), m)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{gcd_mod, resultant};
+    use num_bigint::BigInt;
+
+    fn poly(coefficients: &[i64]) -> Vec<BigInt> {
+        coefficients.iter().map(|&x| BigInt::from(x)).collect()
+    }
+
+    #[test]
+    fn it_eliminates_a_shared_root() {
+        // (x - 2)(x - 3) and (x - 2)(x + 5) share x = 2
+        let p = poly(&[6, -5, 1]);
+        let q = poly(&[-10, 3, 1]);
+        assert_eq!(resultant(&p, &q), BigInt::from(0));
+        // x^2 + 1 and x - 2 have resultant 2^2 + 1
+        assert_eq!(
+            resultant(&poly(&[1, 0, 1]), &poly(&[-2, 1])),
+            BigInt::from(5)
+        );
+        assert_eq!(gcd_mod(&p, &q, &BigInt::from(101)), Some(poly(&[99, 1])));
+    }
+}
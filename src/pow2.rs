@@ -0,0 +1,294 @@
+//! LCGs with an implicit power of two modulus, see [`PowerOfTwoLCG`]
+
+use crate::{LcgError, LcgInt, LCG};
+use num::Signed;
+use num_bigint::BigInt;
+use std::convert::TryFrom;
+
+/// Unsigned types which [`PowerOfTwoLCG`] can do wrapping arithmetic in
+pub trait WrappingInt: LcgInt + Copy {
+    /// Width of the type in bits, the largest modulus it supports is 2^BITS
+    const BITS: u32;
+    /// 0
+    const ZERO: Self;
+    /// 1
+    const ONE: Self;
+
+    /// `x * y` wrapping around at 2^BITS
+    fn wrapping_mul(x: Self, y: Self) -> Self;
+
+    /// `x + y` wrapping around at 2^BITS
+    fn wrapping_add(x: Self, y: Self) -> Self;
+
+    /// `x - y` wrapping around at 2^BITS
+    fn wrapping_sub(x: Self, y: Self) -> Self;
+
+    /// `x & mask`
+    fn and(x: Self, mask: Self) -> Self;
+
+    /// The lowest `bits` bits set, every bit when `bits` is wider than the type
+    fn low_mask(bits: u32) -> Self;
+
+    /// Whether the lowest bit is set
+    fn is_odd(x: Self) -> bool;
+}
+
+macro_rules! impl_wrapping_int {
+    ($($t:ty),*) => {
+        $(
+            impl WrappingInt for $t {
+                const BITS: u32 = <$t>::MAX.count_ones();
+                const ZERO: Self = 0;
+                const ONE: Self = 1;
+
+                fn wrapping_mul(x: Self, y: Self) -> Self {
+                    x.wrapping_mul(y)
+                }
+
+                fn wrapping_add(x: Self, y: Self) -> Self {
+                    x.wrapping_add(y)
+                }
+
+                fn wrapping_sub(x: Self, y: Self) -> Self {
+                    x.wrapping_sub(y)
+                }
+
+                fn and(x: Self, mask: Self) -> Self {
+                    x & mask
+                }
+
+                fn low_mask(bits: u32) -> Self {
+                    match Self::BITS.checked_sub(bits) {
+                        Some(unused) => <$t>::MAX.checked_shr(unused).unwrap_or(0),
+                        None => <$t>::MAX,
+                    }
+                }
+
+                fn is_odd(x: Self) -> bool {
+                    x & 1 == 1
+                }
+            }
+        )*
+    };
+}
+
+impl_wrapping_int!(u32, u64, u128);
+
+/// LCG with modulus 2^bits, such as java.util.Random, drand48 or MMIX
+///
+/// Steps with wrapping multiplication and a bit mask so there is no division or allocation at all.
+/// Converts to and from [`LCG`] and produces the same sequence
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PowerOfTwoLCG<T = u64> {
+    /// Seed
+    pub state: T,
+    /// Multiplier
+    pub a: T,
+    /// Increment
+    pub c: T,
+    /// log2 of the modulus, anywhere from 1 to the width of T, anything wider acts like the width of T
+    pub bits: u32,
+}
+
+impl<T: WrappingInt> PowerOfTwoLCG<T> {
+    /// `2^bits - 1`, anding with it is the same as reducing mod m
+    pub fn mask(&self) -> T {
+        T::low_mask(self.bits)
+    }
+
+    /// Calculate the next value of the LCG
+    ///
+    /// `(state * a + c) & mask`
+    pub fn rand(&mut self) -> T {
+        let next = T::wrapping_add(T::wrapping_mul(self.state, self.a), self.c);
+        self.state = T::and(next, self.mask());
+        self.state
+    }
+
+    /// Calculate the previous value of the LCG
+    ///
+    /// a is only invertible mod 2^bits when it is odd and this will return None otherwise
+    pub fn prev(&mut self) -> Option<T> {
+        let previous = T::wrapping_mul(self.inverse()?, T::wrapping_sub(self.state, self.c));
+        self.state = T::and(previous, self.mask());
+        Some(self.state)
+    }
+
+    /// Move the LCG by `n` steps in O(log n) wrapping multiplications and return the new state
+    ///
+    /// Negative `n` needs a to be odd and will return [`LcgError::NonInvertibleMultiplier`] otherwise
+    pub fn jump(&mut self, n: &BigInt) -> Result<T, LcgError> {
+        let (mut a, mut c) = (self.a, self.c);
+        if n.is_negative() {
+            // the inverse of x -> a*x + c is x -> a^-1*x - a^-1*c
+            a = self.inverse().ok_or(LcgError::NonInvertibleMultiplier)?;
+            c = T::wrapping_sub(T::ZERO, T::wrapping_mul(a, self.c));
+        }
+        let n = n.abs();
+        let (mut step_a, mut step_c) = (T::ONE, T::ZERO);
+        for bit in 0..n.bits() {
+            if n.bit(bit) {
+                step_c = T::wrapping_add(T::wrapping_mul(a, step_c), c);
+                step_a = T::wrapping_mul(a, step_a);
+            }
+            c = T::wrapping_add(T::wrapping_mul(a, c), c);
+            a = T::wrapping_mul(a, a);
+        }
+        let next = T::wrapping_add(T::wrapping_mul(self.state, step_a), step_c);
+        self.state = T::and(next, self.mask());
+        Ok(self.state)
+    }
+
+    /// Inverse of a mod 2^bits by Newton's iteration, each round doubles the number of correct bits
+    fn inverse(&self) -> Option<T> {
+        if !T::is_odd(self.a) {
+            return None;
+        }
+        let two = T::wrapping_add(T::ONE, T::ONE);
+        // an odd a is its own inverse mod 8 so it starts with 3 correct bits
+        let mut inv = self.a;
+        let mut correct = 3;
+        while correct < T::BITS {
+            inv = T::wrapping_mul(inv, T::wrapping_sub(two, T::wrapping_mul(self.a, inv)));
+            correct *= 2;
+        }
+        Some(T::and(inv, self.mask()))
+    }
+}
+
+impl<T: WrappingInt> Iterator for PowerOfTwoLCG<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        Some(self.rand())
+    }
+}
+
+impl<T: WrappingInt> From<PowerOfTwoLCG<T>> for LCG {
+    /// m is 2^bits, or 2^BITS when `bits` is wider than T, with every parameter reduced into [0, m) so both step the same
+    fn from(lcg: PowerOfTwoLCG<T>) -> LCG {
+        let reduce = |x: T| T::and(x, lcg.mask()).to_big();
+        LCG {
+            state: reduce(lcg.state),
+            a: reduce(lcg.a),
+            c: reduce(lcg.c),
+            m: BigInt::from(1) << lcg.bits.min(T::BITS) as usize,
+        }
+    }
+}
+
+impl<T: WrappingInt> TryFrom<LCG> for PowerOfTwoLCG<T> {
+    type Error = LcgError;
+
+    /// Fails with [`LcgError::ModulusNotPowerOfTwo`] unless m is a power of two no wider than T, parameters are reduced mod m
+    fn try_from(lcg: LCG) -> Result<Self, LcgError> {
+        let bits = lcg.m.trailing_zeros().unwrap_or(0);
+        if lcg.m != BigInt::from(1) << bits as usize || bits == 0 || bits > u64::from(T::BITS) {
+            return Err(LcgError::ModulusNotPowerOfTwo);
+        }
+        let reduce = |x: &BigInt| {
+            T::from_big(&crate::modulo(x, &lcg.m)).ok_or(LcgError::ModulusNotPowerOfTwo)
+        };
+        Ok(PowerOfTwoLCG {
+            state: reduce(&lcg.state)?,
+            a: reduce(&lcg.a)?,
+            c: reduce(&lcg.c)?,
+            bits: bits as u32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{PowerOfTwoLCG, LCG};
+    use num_bigint::{BigInt, ToBigInt};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn it_matches_the_bigint_lcg() {
+        let mut java = PowerOfTwoLCG {
+            state: 0x1234_5678u64,
+            a: 0x5DEECE66D,
+            c: 11,
+            bits: 48,
+        };
+        let mut big = LCG::from(java);
+        assert_eq!(big.m, (1u64 << 48).to_bigint().unwrap());
+        let forward = (&mut java).take(1000).collect::<Vec<_>>();
+        assert_eq!(
+            forward
+                .iter()
+                .map(|x| x.to_bigint().unwrap())
+                .collect::<Vec<_>>(),
+            (&mut big).take(1000).collect::<Vec<_>>()
+        );
+        assert_eq!(java.prev(), Some(forward[998]));
+        assert_eq!(java.jump(&(-998).to_bigint().unwrap()), Ok(forward[0]));
+        assert_eq!(java.jump(&998.to_bigint().unwrap()), Ok(forward[998]));
+
+        let far = 10.to_bigint().unwrap().pow(18);
+        java.jump(&far).unwrap();
+        big.jump(&(&far - 1)).unwrap();
+        assert_eq!(java.state.to_bigint().unwrap(), big.state);
+    }
+
+    #[test]
+    fn it_converts_full_width_moduli() {
+        let mmix = LCG {
+            state: 42.to_bigint().unwrap(),
+            a: 6364136223846793005u64.to_bigint().unwrap(),
+            c: 1442695040888963407u64.to_bigint().unwrap(),
+            m: BigInt::from(1) << 64,
+        };
+        let mut native = PowerOfTwoLCG::<u64>::try_from(mmix.clone()).unwrap();
+        assert_eq!(native.bits, 64);
+        assert_eq!(
+            mmix.clone().take(100).collect::<Vec<_>>(),
+            (&mut native)
+                .take(100)
+                .map(|x| x.to_bigint().unwrap())
+                .collect::<Vec<_>>()
+        );
+        assert!(PowerOfTwoLCG::<u32>::try_from(mmix.clone()).is_err());
+        // wider than the type wraps like the full width instead of underflowing the mask
+        let mut wide = PowerOfTwoLCG {
+            state: 1u32,
+            a: 5,
+            c: 3,
+            bits: 40,
+        };
+        assert_eq!(wide.mask(), u32::MAX);
+        assert_eq!(LCG::from(wide).m, BigInt::from(1) << 32);
+        assert_eq!(
+            LCG::from(wide).take(50).collect::<Vec<_>>(),
+            wide.take(50).map(BigInt::from).collect::<Vec<_>>()
+        );
+        assert_eq!(wide.rand(), 8);
+        assert_eq!(wide.prev(), Some(1));
+        // parameters past the mask are reduced on the way over
+        let narrow = PowerOfTwoLCG {
+            state: 100u32,
+            a: 13,
+            c: 11,
+            bits: 3,
+        };
+        assert_eq!(
+            LCG::from(narrow),
+            LCG {
+                state: 4.into(),
+                a: 5.into(),
+                c: 3.into(),
+                m: 8.into()
+            }
+        );
+        assert_eq!(
+            LCG::from(narrow).take(20).collect::<Vec<_>>(),
+            narrow.take(20).map(BigInt::from).collect::<Vec<_>>()
+        );
+        assert!(PowerOfTwoLCG::<u64>::try_from(LCG {
+            m: 479001599.to_bigint().unwrap(),
+            ..mmix
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,322 @@
+//! Division free stepping for large odd moduli, see [`MontgomeryLCG`]
+
+use crate::{modinv, modulo, LcgError, LCG};
+use num::{Integer, Signed};
+use num_bigint::{BigInt, Sign};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+/// Precomputed Montgomery reduction for a fixed odd modulus
+///
+/// Numbers are little endian u64 limbs as wide as m. Multipliers are kept in Montgomery form `a * R mod m` with `R = 2^(64 * limbs)`,
+/// so multiplying one into a plain state with [`mul`](Montgomery::mul) gives a plain state back and nothing has to be converted per step
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Montgomery {
+    modulus: BigInt,
+    m: Vec<u64>,
+    /// -m^-1 mod 2^64
+    m_prime: u64,
+}
+
+impl Montgomery {
+    /// The context for an odd `m` > 0
+    fn new(modulus: &BigInt) -> Montgomery {
+        let (_, m) = modulus.to_u64_digits();
+        // Newton's iteration doubles the correct low bits of m^-1 every step, starting from the 1 that any odd m has
+        let mut inverse = 1u64;
+        for _ in 0..6 {
+            inverse = inverse.wrapping_mul(2u64.wrapping_sub(m[0].wrapping_mul(inverse)));
+        }
+        Montgomery {
+            modulus: modulus.clone(),
+            m_prime: inverse.wrapping_neg(),
+            m,
+        }
+    }
+
+    /// `x` reduced mod m as limbs, this one divides so it's only used when setting up
+    fn limbs(&self, x: &BigInt) -> Vec<u64> {
+        let (_, mut limbs) = modulo(x, &self.modulus).to_u64_digits();
+        limbs.resize(self.m.len(), 0);
+        limbs
+    }
+
+    /// `x` reduced mod m in Montgomery form
+    fn encode(&self, x: &BigInt) -> Vec<u64> {
+        self.limbs(&(x << (64 * self.m.len())))
+    }
+
+    /// Back out of Montgomery form
+    fn decode(&self, x: &[u64]) -> Vec<u64> {
+        let mut one = vec![0; self.m.len()];
+        one[0] = 1;
+        self.mul(x, &one)
+    }
+
+    /// The value of plain limbs
+    fn to_big(x: &[u64]) -> BigInt {
+        let mut digits = Vec::with_capacity(2 * x.len());
+        for &limb in x {
+            digits.push(limb as u32);
+            digits.push((limb >> 32) as u32);
+        }
+        BigInt::from_slice(Sign::Plus, &digits)
+    }
+
+    /// Subtract m once if `t`, one limb wider than m, is at least m. Anything below 2m ends up reduced
+    fn reduce_once(&self, mut t: Vec<u64>) -> Vec<u64> {
+        let n = self.m.len();
+        let below = t[n] == 0 && t[..n].iter().rev().cmp(self.m.iter().rev()) == Ordering::Less;
+        if !below {
+            let mut borrow = false;
+            for (limb, m) in t.iter_mut().zip(&self.m) {
+                let (difference, under) = limb.overflowing_sub(*m);
+                let (difference, under_again) = difference.overflowing_sub(u64::from(borrow));
+                *limb = difference;
+                borrow = under || under_again;
+            }
+        }
+        t.truncate(n);
+        t
+    }
+
+    /// `x * y / R mod m` for x and y already reduced, word by word so nothing divides
+    fn mul(&self, x: &[u64], y: &[u64]) -> Vec<u64> {
+        let m = &self.m[..];
+        let n = m.len();
+        let x = &x[..n];
+        let mut t = vec![0u64; n + 2];
+        for &yi in &y[..n] {
+            let mut carry = 0u128;
+            for (tj, &xj) in t.iter_mut().zip(x) {
+                let sum = u128::from(*tj) + u128::from(xj) * u128::from(yi) + carry;
+                *tj = sum as u64;
+                carry = sum >> 64;
+            }
+            let sum = u128::from(t[n]) + carry;
+            t[n] = sum as u64;
+            t[n + 1] = (sum >> 64) as u64;
+
+            // adding u*m clears the lowest limb, which is then shifted out
+            let u = t[0].wrapping_mul(self.m_prime);
+            let mut carry = (u128::from(t[0]) + u128::from(u) * u128::from(m[0])) >> 64;
+            for j in 1..n {
+                let sum = u128::from(t[j]) + u128::from(u) * u128::from(m[j]) + carry;
+                t[j - 1] = sum as u64;
+                carry = sum >> 64;
+            }
+            let sum = u128::from(t[n]) + carry;
+            t[n - 1] = sum as u64;
+            t[n] = t[n + 1] + (sum >> 64) as u64;
+            t[n + 1] = 0;
+        }
+        t.truncate(n + 1);
+        self.reduce_once(t)
+    }
+
+    /// `x + y mod m` for x and y already reduced
+    fn add(&self, x: &[u64], y: &[u64]) -> Vec<u64> {
+        let mut t = Vec::with_capacity(x.len() + 1);
+        let mut carry = false;
+        for (a, b) in x.iter().zip(y) {
+            let (sum, over) = a.overflowing_add(*b);
+            let (sum, over_again) = sum.overflowing_add(u64::from(carry));
+            t.push(sum);
+            carry = over || over_again;
+        }
+        t.push(u64::from(carry));
+        self.reduce_once(t)
+    }
+
+    /// `(a1, c1) ∘ (a2, c2)` as affine maps, with the multipliers in Montgomery form
+    fn compose(
+        &self,
+        (a1, c1): &(Vec<u64>, Vec<u64>),
+        (a2, c2): &(Vec<u64>, Vec<u64>),
+    ) -> (Vec<u64>, Vec<u64>) {
+        (self.mul(a1, a2), self.add(&self.mul(a1, c2), c1))
+    }
+}
+
+/// LCG with a precomputed Montgomery reduction context, so stepping, jumping and generating never divide
+///
+/// Works on u64 limbs rather than going through [`BigInt`] so it pays off for big moduli, such as 256 or 1024 bits.
+/// Produces exactly the same values as the [`LCG`] it was built from. Montgomery reduction only works for odd moduli, see [`PowerOfTwoLCG`](crate::PowerOfTwoLCG) for powers of two
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MontgomeryLCG {
+    state: Vec<u64>,
+    /// in Montgomery form, the state and c aren't
+    a: Vec<u64>,
+    c: Vec<u64>,
+    /// the inverse affine map, if a is invertible
+    inverse: Option<(Vec<u64>, Vec<u64>)>,
+    reduction: Montgomery,
+}
+
+impl TryFrom<&LCG> for MontgomeryLCG {
+    type Error = LcgError;
+
+    /// Fails with [`LcgError::NonPositiveModulus`] or [`LcgError::EvenModulus`] unless m is odd and positive, parameters are reduced mod m
+    fn try_from(lcg: &LCG) -> Result<MontgomeryLCG, LcgError> {
+        let m = &lcg.m;
+        if !m.is_positive() {
+            return Err(LcgError::NonPositiveModulus);
+        }
+        if m.is_even() {
+            return Err(LcgError::EvenModulus);
+        }
+        let reduction = Montgomery::new(m);
+        let a = modulo(&lcg.a, m);
+        let inverse = modinv(&a, m).map(|a_inv| {
+            let c_inv = -(&a_inv * &lcg.c);
+            (reduction.encode(&a_inv), reduction.limbs(&c_inv))
+        });
+        Ok(MontgomeryLCG {
+            state: reduction.limbs(&lcg.state),
+            a: reduction.encode(&a),
+            c: reduction.limbs(&lcg.c),
+            inverse,
+            reduction,
+        })
+    }
+}
+
+impl From<MontgomeryLCG> for LCG {
+    fn from(lcg: MontgomeryLCG) -> LCG {
+        let reduction = &lcg.reduction;
+        LCG {
+            state: Montgomery::to_big(&lcg.state),
+            a: Montgomery::to_big(&reduction.decode(&lcg.a)),
+            c: Montgomery::to_big(&lcg.c),
+            m: reduction.modulus.clone(),
+        }
+    }
+}
+
+impl Iterator for MontgomeryLCG {
+    type Item = BigInt;
+
+    fn next(&mut self) -> Option<BigInt> {
+        Some(self.rand())
+    }
+}
+
+impl MontgomeryLCG {
+    /// Current state
+    pub fn state(&self) -> BigInt {
+        Montgomery::to_big(&self.state)
+    }
+
+    /// Calculate the next value of the LCG
+    pub fn rand(&mut self) -> BigInt {
+        let reduction = &self.reduction;
+        self.state = reduction.add(&reduction.mul(&self.state, &self.a), &self.c);
+        self.state()
+    }
+
+    /// Calculate the next `n` values of the LCG in one go
+    pub fn generate(&mut self, n: usize) -> Vec<BigInt> {
+        (0..n).map(|_| self.rand()).collect()
+    }
+
+    /// Advance the LCG by `n` steps in O(log n) multiplications and return the new state, like [`LCG::skip`]
+    ///
+    /// Method syntax on an owned `MontgomeryLCG` resolves to [`Iterator::skip`] so call it as `MontgomeryLCG::skip(&mut lcg, &n)` or through a `&mut MontgomeryLCG`
+    ///
+    /// Panics if `n` is negative
+    pub fn skip(&mut self, n: &BigInt) -> BigInt {
+        assert!(!n.is_negative(), "cannot skip a negative number of steps");
+        let base = (self.a.clone(), self.c.clone());
+        self.apply(base, n)
+    }
+
+    /// Move the LCG by `n` steps in O(log n) multiplications and return the new state, like [`LCG::jump`]
+    ///
+    /// Negative jumps need modinv(a,m) to exist and will return [`LcgError::NonInvertibleMultiplier`] otherwise
+    pub fn jump(&mut self, n: &BigInt) -> Result<BigInt, LcgError> {
+        if !n.is_negative() {
+            return Ok(MontgomeryLCG::skip(self, n));
+        }
+        let base = self
+            .inverse
+            .clone()
+            .ok_or(LcgError::NonInvertibleMultiplier)?;
+        Ok(self.apply(base, &-n))
+    }
+
+    /// Apply `base` to the state `n` times, by square and multiply over the map
+    fn apply(&mut self, mut base: (Vec<u64>, Vec<u64>), n: &BigInt) -> BigInt {
+        let reduction = &self.reduction;
+        let mut step = (reduction.encode(&1.into()), vec![0; reduction.m.len()]);
+        for bit in 0..n.bits() {
+            if n.bit(bit) {
+                step = reduction.compose(&base, &step);
+            }
+            base = reduction.compose(&base, &base);
+        }
+        self.state = reduction.add(&reduction.mul(&step.0, &self.state), &step.1);
+        self.state()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LcgError, MontgomeryLCG, LCG};
+    use num::Zero;
+    use num_bigint::{BigInt, ToBigInt};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn it_matches_the_dividing_lcg() {
+        for m in &[
+            (BigInt::from(1) << 255) - 19,
+            (BigInt::from(1) << 521) - 1,
+            BigInt::from(2147483647),
+        ] {
+            let lcg = LCG {
+                state: m - 1,
+                a: m - BigInt::from(12345).pow(31) % m,
+                c: m - 7,
+                m: m.clone(),
+            };
+            let mut fast = MontgomeryLCG::try_from(&lcg).unwrap();
+            let mut slow = lcg.clone();
+            assert_eq!(
+                fast.generate(1000),
+                (&mut slow).take(1000).collect::<Vec<_>>()
+            );
+
+            let far = 10.to_bigint().unwrap().pow(30);
+            assert_eq!(
+                MontgomeryLCG::skip(&mut fast, &far),
+                LCG::skip(&mut slow, &far)
+            );
+            let back = -far - 1000;
+            assert_eq!(fast.jump(&back).unwrap(), slow.jump(&back).unwrap());
+            assert_eq!(fast.state(), lcg.state);
+            assert_eq!(MontgomeryLCG::skip(&mut fast, &BigInt::zero()), lcg.state);
+            assert_eq!(LCG::from(fast), lcg);
+        }
+    }
+
+    #[test]
+    fn it_rejects_what_it_cant_reduce() {
+        let lcg = LCG {
+            state: 7.to_bigint().unwrap(),
+            a: 6.to_bigint().unwrap(),
+            c: 1.to_bigint().unwrap(),
+            m: 16.to_bigint().unwrap(),
+        };
+        assert_eq!(MontgomeryLCG::try_from(&lcg), Err(LcgError::EvenModulus));
+        let lcg = LCG {
+            m: 15.to_bigint().unwrap(),
+            ..lcg
+        };
+        let mut fast = MontgomeryLCG::try_from(&lcg).unwrap();
+        assert_eq!(fast.generate(3), lcg.clone().take(3).collect::<Vec<_>>());
+        assert_eq!(
+            fast.jump(&(-1).to_bigint().unwrap()),
+            Err(LcgError::NonInvertibleMultiplier)
+        );
+    }
+}
@@ -0,0 +1,314 @@
+//! Outputs reduced mod a small n like dice rolls, card draws or roulette spins, see [`recover_state_reduced`]
+
+use crate::crack::to_bigints;
+use crate::math::ratio;
+use crate::truncated::state_congruences;
+use crate::{solve_bounded, LCG};
+use num::{One, Signed, ToPrimitive};
+use num_bigint::BigInt;
+
+/// Never go through more states than this matching the first output one by one, well under a minute in release
+const BRUTE_FORCE_BOUND: u64 = 1 << 32;
+
+/// Roughly how many states brute force checks in the time one lattice takes
+const LATTICE_COST: u64 = 1 << 20;
+
+/// How many bits more than m has the lattice is given, so a wrong state is unlikely to fit and reduction has some room
+const MARGIN_BITS: f64 = 12.0;
+
+/// Give up rather than guess more quotient bits than this
+const MAX_GUESS_BITS: u32 = 12;
+
+/// Outcome of [`recover_state_reduced`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReducedRecovery {
+    /// Every LCG consistent with the outputs, with the state set to the full state behind the first output
+    pub seeds: Vec<LCG>,
+    /// `expected[i]` is how many states are expected to fit the first i+1 outputs, the real one included
+    pub expected: Vec<f64>,
+}
+
+/// Roughly how many bits of the state `count` outputs give away
+///
+/// Each is worth log2(n) bits, except that with m a power of two the part of n that's a power of two, 2^e, only sees bits shift to shift+e of each state.
+/// Those only depend on the lowest shift+e bits of the first state so they can't give away more than that between them
+fn information(m: &BigInt, shift: u32, n: u64, count: usize) -> f64 {
+    let count = count as f64;
+    let e = if m.trailing_zeros() == Some(m.bits() - 1) {
+        n.trailing_zeros()
+    } else {
+        0
+    };
+    let odd = (n >> e) as f64;
+    count * odd.log2() + (count * f64::from(e)).min(f64::from(shift + e))
+}
+
+/// How many of the outputs the lattice uses and how many quotient bits it has to guess on top, or None if that's more than [`MAX_GUESS_BITS`]
+fn plan(m: &BigInt, shift: u32, n: u64, outputs: usize) -> Option<(usize, u32)> {
+    let wanted = m.bits() as f64 + MARGIN_BITS;
+    let count = (2..outputs)
+        .find(|&count| information(m, shift, n, count) >= wanted)
+        .unwrap_or(outputs);
+    let missing = (wanted - information(m, shift, n, count)).ceil();
+    if missing > f64::from(MAX_GUESS_BITS) {
+        return None;
+    }
+    Some((count, missing.max(0.0) as u32))
+}
+
+/// Whether the state `x0` followed by `lcg` gives `outputs` as `(state >> shift) % n`
+fn reproduces(lcg: &LCG, x0: &BigInt, shift: u32, n: &BigInt, outputs: &[BigInt]) -> bool {
+    lcg.states_from(x0)
+        .zip(outputs)
+        .all(|(state, y)| &((state >> shift as usize) % n) == y)
+}
+
+/// Every state matching the outputs when m fits in a u64, by trying each one that matches the first
+///
+/// Only when that's fewer than [`BRUTE_FORCE_BOUND`] states and cheaper than the `2^guessed` lattices [`guided`] would go through instead
+fn brute_force(
+    lcg: &LCG,
+    shift: u32,
+    n: u64,
+    outputs: &[BigInt],
+    guessed: Option<u32>,
+) -> Option<Vec<BigInt>> {
+    let (m, a, c) = (lcg.m.to_u64()?, lcg.a.to_u64()?, lcg.c.to_u64()?);
+    let lows = 1u64.checked_shl(shift)?;
+    let outputs = outputs
+        .iter()
+        .map(ToPrimitive::to_u64)
+        .collect::<Option<Vec<_>>>()?;
+    let states = (u128::from(m >> shift) / u128::from(n) + 1) * u128::from(lows);
+    let lattices = guessed.map_or(u128::MAX, |bits| u128::from(LATTICE_COST) << bits);
+    if states > u128::from(BRUTE_FORCE_BOUND) || states > lattices {
+        return None;
+    }
+    let step = |x: u64| ((u128::from(a) * u128::from(x) + u128::from(c)) % u128::from(m)) as u64;
+    let mut seeds = vec![];
+    let mut high = outputs[0];
+    while high <= (m - 1) >> shift {
+        for low in 0..lows {
+            let x0 = (high << shift) + low;
+            if x0 >= m {
+                break;
+            }
+            let mut x = x0;
+            let fits = outputs[1..].iter().all(|&y| {
+                x = step(x);
+                (x >> shift) % n == y
+            });
+            if fits {
+                seeds.push(BigInt::from(x0));
+            }
+        }
+        high += n;
+    }
+    Some(seeds)
+}
+
+/// The state behind the first output, given the top `guessed[i]` bits of each output's quotient as `guesses[i]`
+///
+/// Every state is `2^shift * (n * quotient + output) + low` with `x_i = A_i * x_0 + C_i mod m`, so what's left of the quotients and
+/// the low bits are a small solution of linear congruences, see [`solve_bounded`]
+fn state_from_guess(
+    lcg: &LCG,
+    shift: u32,
+    n: &BigInt,
+    outputs: &[BigInt],
+    guesses: &[(BigInt, u32)],
+) -> Option<BigInt> {
+    let m = &lcg.m;
+    let quotients = (((m - 1u32) >> shift as usize) / n) + 1u32;
+    let quotient_bits = quotients.bits() as u32;
+    let unit = n << shift as usize;
+    let mut units = vec![unit.clone()];
+    if shift > 0 {
+        units.push(BigInt::one());
+    }
+    let mut known = vec![];
+    let mut bounds = vec![];
+    for (y, (guess, bits)) in outputs.iter().zip(guesses) {
+        let rest = quotient_bits - bits;
+        let base = guess << rest as usize;
+        let bound = (BigInt::one() << rest as usize).min(&quotients - &base);
+        if !bound.is_positive() {
+            return None;
+        }
+        known.push(&base * &unit + (y << shift as usize));
+        bounds.push(bound);
+        if shift > 0 {
+            bounds.push(BigInt::one() << shift as usize);
+        }
+    }
+
+    let (rows, targets) = state_congruences(lcg, &known, &units);
+    let z = solve_bounded(&rows, &targets, m, &bounds)?;
+    Some(
+        &known[0]
+            + units
+                .iter()
+                .zip(&z)
+                .map(|(unit, zi)| unit * zi)
+                .sum::<BigInt>(),
+    )
+}
+
+/// Every state matching the outputs, guessing the top bits of a few quotients until the rest is small enough for a lattice
+fn guided(lcg: &LCG, shift: u32, n: &BigInt, outputs: &[BigInt]) -> Option<Vec<BigInt>> {
+    let (count, missing) = plan(&lcg.m, shift, n.to_u64()?, outputs.len())?;
+    let used = &outputs[..count];
+
+    let quotients = (((&lcg.m - 1u32) >> shift as usize) / n) + 1u32;
+    let quotient_bits = quotients.bits() as u32;
+    // spread the guessed bits over the outputs
+    let mut guessed = vec![0u32; used.len()];
+    for i in 0..missing as usize {
+        let slot = i % used.len();
+        if guessed[slot] < quotient_bits {
+            guessed[slot] += 1;
+        }
+    }
+    let total = guessed.iter().sum::<u32>();
+
+    let mut seeds = vec![];
+    for guess in 0u64..1 << total {
+        let mut rest = guess;
+        let guesses = guessed
+            .iter()
+            .map(|&bits| {
+                let part = rest & ((1 << bits) - 1);
+                rest >>= bits;
+                (BigInt::from(part), bits)
+            })
+            .collect::<Vec<_>>();
+        if let Some(x0) = state_from_guess(lcg, shift, n, used, &guesses) {
+            if reproduces(lcg, &x0, shift, n, outputs) && !seeds.contains(&x0) {
+                seeds.push(x0);
+            }
+        }
+    }
+    Some(seeds)
+}
+
+/// Recover every state of a known LCG consistent with consecutive outputs `(state >> shift) % n`
+///
+/// Each output pins down all but the quotient and the low `shift` bits, which [`solve_bounded`] finds once there are enough outputs,
+/// guessing the top bits of a few quotients when there are a few too few. When m fits in a u64 and trying every state matching the first output
+/// is cheaper than that, or the only option, that's done instead as long as there are at most 2^32 of them, which takes a few seconds for minstd and a die.
+/// Around `(bits of m + 12) / log2(n)` outputs is enough to skip guessing, such as 17 rolls of a die for minstd.
+///
+/// The lattice only finds one state per guess, so it can miss some when [`expected`](ReducedRecovery::expected) is well above 1, but everything returned fits.
+/// Only `a`, `c` and `m` of `lcg` are used. Returns None for `n < 2`, an output of n or more, or too few outputs to even guess from
+pub fn recover_state_reduced<T: Clone + Into<BigInt>>(
+    lcg: &LCG,
+    shift: u32,
+    n: u64,
+    outputs: &[T],
+) -> Option<ReducedRecovery> {
+    let outputs = to_bigints(outputs);
+    let big_n = BigInt::from(n);
+    if n < 2
+        || outputs.is_empty()
+        || !lcg.m.is_positive()
+        || outputs.iter().any(|y| y.is_negative() || y >= &big_n)
+    {
+        return None;
+    }
+    let expected = (1..=outputs.len())
+        .map(|count| {
+            let bits = information(&lcg.m, shift, n, count);
+            let whole = BigInt::one() << bits as usize;
+            1.0 + ratio(&(&lcg.m - 1u32), &whole) * (bits.floor() - bits).exp2()
+        })
+        .collect();
+    let guessed = plan(&lcg.m, shift, n, outputs.len()).map(|(_, missing)| missing);
+    let seeds = match brute_force(lcg, shift, n, &outputs, guessed) {
+        Some(seeds) => seeds,
+        None => guided(lcg, shift, &big_n, &outputs)?,
+    };
+    Some(ReducedRecovery {
+        seeds: seeds
+            .into_iter()
+            .map(|state| LCG {
+                state,
+                ..lcg.clone()
+            })
+            .collect(),
+        expected,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{recover_state_reduced, LCG};
+    use num_bigint::BigInt;
+
+    #[test]
+    fn it_brute_forces_small_moduli() {
+        let mut rand = LCG {
+            state: BigInt::from(123456),
+            a: 16807.into(),
+            c: 0.into(),
+            m: 1000003.into(),
+        };
+        let states = (&mut rand).take(12).collect::<Vec<_>>();
+        let rolls = states.iter().map(|x| x % 6).collect::<Vec<_>>();
+
+        let few = recover_state_reduced(&rand, 0, 6, &rolls[..4]).unwrap();
+        assert!(few.seeds.iter().any(|lcg| lcg.state == states[0]));
+        // 1 + 1000002/6^4
+        assert!((few.expected[3] - 772.6).abs() < 0.1);
+        assert!(few.seeds.len() > 500 && few.seeds.len() < 1100);
+
+        let all = recover_state_reduced(&rand, 0, 6, &rolls).unwrap();
+        assert_eq!(all.seeds.len(), 1);
+        assert_eq!(all.seeds[0].state, states[0]);
+        assert!(all.expected[11] < 1.001);
+        assert_eq!(recover_state_reduced(&rand, 0, 6, &[6]), None);
+
+        // far too few rolls for the lattice, but 2^26/6 states are quick to go through
+        let mut rand = LCG {
+            m: 67108859.into(),
+            ..rand
+        };
+        let states = (&mut rand).take(12).collect::<Vec<_>>();
+        let rolls = states.iter().map(|x| x % 6).collect::<Vec<_>>();
+        let recovered = recover_state_reduced(&rand, 0, 6, &rolls).unwrap();
+        assert_eq!(recovered.seeds.len(), 1);
+        assert_eq!(recovered.seeds[0].state, states[0]);
+    }
+
+    #[test]
+    fn it_recovers_states_with_a_lattice() {
+        let mut minstd = LCG {
+            state: BigInt::from(20240601),
+            a: 16807.into(),
+            c: 0.into(),
+            m: 2147483647.into(),
+        };
+        let states = (&mut minstd).take(20).collect::<Vec<_>>();
+        let rolls = states.iter().map(|x| x % 6).collect::<Vec<_>>();
+        let recovered = recover_state_reduced(&minstd, 0, 6, &rolls).unwrap();
+        assert_eq!(recovered.seeds.len(), 1);
+        assert_eq!(recovered.seeds[0].state, states[0]);
+        // a couple of rolls short, the missing bits get guessed
+        let recovered = recover_state_reduced(&minstd, 0, 6, &rolls[..16]).unwrap();
+        assert_eq!(recovered.seeds[0].state, states[0]);
+
+        // java.util.Random::nextInt(52) is (state >> 17) % 52
+        let mut java = LCG {
+            state: BigInt::from(0x1234_5678_9ABCu64),
+            a: 0x5DEECE66Du64.into(),
+            c: 11.into(),
+            m: BigInt::from(1) << 48,
+        };
+        let states = (&mut java).take(16).collect::<Vec<_>>();
+        let cards = states.iter().map(|x| (x >> 17) % 52).collect::<Vec<_>>();
+        let recovered = recover_state_reduced(&java, 17, 52, &cards).unwrap();
+        assert_eq!(recovered.seeds.len(), 1);
+        assert_eq!(recovered.seeds[0].state, states[0]);
+        // the factor of 4 in 52 only ever sees the lowest 19 bits
+        assert!(recovered.expected[15] > 1.0 + 0.9 * 2f64.powf(48.0 - 19.0 - 16.0 * 13f64.log2()));
+    }
+}
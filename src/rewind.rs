@@ -0,0 +1,206 @@
+//! Rewinding LCGs whose multiplier isn't invertible, see [`LCG::predecessors`]
+
+use crate::crack::MAX_CANDIDATES;
+use crate::{math, modulo, LCG};
+use num::ToPrimitive;
+use num_bigint::BigInt;
+
+/// Every way of rewinding an LCG by a fixed number of steps, built by [`LCG::predecessor_tree`]
+///
+/// Branches which can't be rewound all the way are pruned, so every leaf is exactly `depth` steps before the root.
+/// If nothing can be rewound that far the tree is just the root, with no leaves or paths at all
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PredecessorTree {
+    /// State at this node
+    pub state: BigInt,
+    /// How many steps back from this node the leaves are
+    pub depth: usize,
+    /// Every state which steps into this one and can itself be rewound far enough
+    pub children: Vec<PredecessorTree>,
+}
+
+impl PredecessorTree {
+    /// Every candidate for the state `depth` steps back
+    pub fn leaves(&self) -> Vec<BigInt> {
+        if self.depth == 0 {
+            return vec![self.state.clone()];
+        }
+        self.children.iter().flat_map(|x| x.leaves()).collect()
+    }
+
+    /// Every candidate sequence of states, oldest first and ending with the root
+    pub fn paths(&self) -> Vec<Vec<BigInt>> {
+        if self.depth == 0 {
+            return vec![vec![self.state.clone()]];
+        }
+        self.children
+            .iter()
+            .flat_map(|x| x.paths())
+            .map(|mut path| {
+                path.push(self.state.clone());
+                path
+            })
+            .collect()
+    }
+}
+
+impl LCG {
+    /// Every state which steps into the current one
+    ///
+    /// Solves `a*x ≡ state - c (mod m)`, which has either no solutions or gcd(a, m) of them.
+    /// Unlike [`prev`](LCG::prev) this works when a isn't invertible, but gcd(a, m) can be huge (a = 0 makes every state a predecessor of c)
+    /// so this returns None when there are more than 65536
+    pub fn predecessors(&self) -> Option<Vec<BigInt>> {
+        match math::solve_linear(&self.a, &(&self.state - &self.c), &self.m) {
+            Some((first, step)) => {
+                let count = (&self.m / &step)
+                    .to_usize()
+                    .filter(|&x| x <= MAX_CANDIDATES)?;
+                Some(
+                    (0..count)
+                        .map(|k| modulo(&(&first + &step * k), &self.m))
+                        .collect(),
+                )
+            }
+            None => Some(vec![]),
+        }
+    }
+
+    /// Rewind `depth` steps, branching on every ambiguous predecessor
+    ///
+    /// The tree grows by up to a factor of gcd(a, m) per level, so this gives up and returns None once it has been through more than 65536 states
+    pub fn predecessor_tree(&self, depth: usize) -> Option<PredecessorTree> {
+        let state = modulo(&self.state, &self.m);
+        let mut budget = MAX_CANDIDATES;
+        Some(
+            self.rewind_from(&state, depth, &mut budget)?
+                .unwrap_or(PredecessorTree {
+                    state,
+                    depth,
+                    children: vec![],
+                }),
+        )
+    }
+
+    /// The subtree under `state`, or Some(None) if it can't be rewound `depth` steps
+    ///
+    /// Every predecessor looked at is taken out of `budget` and None means it ran out
+    fn rewind_from(
+        &self,
+        state: &BigInt,
+        depth: usize,
+        budget: &mut usize,
+    ) -> Option<Option<PredecessorTree>> {
+        let node = LCG {
+            state: state.clone(),
+            ..self.clone()
+        };
+        let mut children = vec![];
+        if depth > 0 {
+            let predecessors = node.predecessors()?;
+            *budget = budget.checked_sub(predecessors.len())?;
+            for x in &predecessors {
+                children.extend(self.rewind_from(x, depth - 1, budget)?);
+            }
+            if children.is_empty() {
+                return Some(None);
+            }
+        }
+        Some(Some(PredecessorTree {
+            state: node.state,
+            depth,
+            children,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::LCG;
+    use num_bigint::{BigInt, ToBigInt};
+
+    fn lcg(state: u32, a: u32, c: u32, m: u32) -> LCG {
+        LCG {
+            state: state.to_bigint().unwrap(),
+            a: a.to_bigint().unwrap(),
+            c: c.to_bigint().unwrap(),
+            m: m.to_bigint().unwrap(),
+        }
+    }
+
+    #[test]
+    fn it_finds_every_predecessor() {
+        for state in 0..64 {
+            let rand = lcg(state, 6, 3, 64);
+            let mut predecessors = rand.predecessors().unwrap();
+            predecessors.sort();
+            let expected = (0..64u32)
+                .filter(|&x| (x * 6 + 3) % 64 == state)
+                .map(BigInt::from)
+                .collect::<Vec<_>>();
+            assert_eq!(predecessors, expected);
+        }
+        assert_eq!(lcg(5, 7, 1, 64).predecessors().unwrap().len(), 1);
+
+        // a = 0 sends every state to c, and a = 2^20 leaves 2^20 predecessors for each
+        let zero = LCG {
+            state: 3.into(),
+            a: 0.into(),
+            c: 3.into(),
+            m: BigInt::from(1) << 40,
+        };
+        assert_eq!(zero.predecessors(), None);
+        assert_eq!(zero.predecessor_tree(1), None);
+        assert_eq!(
+            LCG {
+                state: 1.into(),
+                ..zero.clone()
+            }
+            .predecessors(),
+            Some(vec![])
+        );
+        let wide = LCG {
+            a: BigInt::from(1) << 20,
+            ..zero
+        };
+        assert_eq!(wide.predecessors(), None);
+        assert_eq!(wide.predecessor_tree(2), None);
+        // 2^10 per level is fine on its own but not twice over
+        let narrow = LCG {
+            state: 0.into(),
+            a: BigInt::from(1) << 10,
+            c: 0.into(),
+            ..wide
+        };
+        assert_eq!(narrow.predecessors().unwrap().len(), 1 << 10);
+        assert_eq!(narrow.predecessor_tree(1).unwrap().leaves().len(), 1 << 10);
+        assert_eq!(narrow.predecessor_tree(2), None);
+    }
+
+    #[test]
+    fn it_rewinds_through_ambiguity() {
+        let mut rand = lcg(11, 6, 3, 64);
+        let forward = (&mut rand).take(3).collect::<Vec<_>>();
+        let tree = rand.predecessor_tree(3).unwrap();
+        let paths = tree.paths();
+        assert!(paths.contains(&vec![
+            11.to_bigint().unwrap(),
+            forward[0].clone(),
+            forward[1].clone(),
+            forward[2].clone()
+        ]));
+        for path in paths {
+            let replay = LCG {
+                state: path[0].clone(),
+                ..rand.clone()
+            };
+            assert_eq!(replay.take(3).collect::<Vec<_>>(), path[1..].to_vec());
+        }
+        assert_eq!(tree.leaves().len(), tree.paths().len());
+        // 3 is 6*x+3 only for x ≡ 0 mod 32 but 0 and 32 themselves have no predecessors
+        assert_eq!(
+            lcg(3, 6, 3, 64).predecessor_tree(2).unwrap().children,
+            vec![]
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! Cracking through corrupted observations, see [`crack_lcg_robust`]
+
+use crate::crack::to_bigints;
+use crate::{crack_lcg_all, LCG};
+use num_bigint::BigInt;
+
+/// Outcome of [`crack_lcg_robust`]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RobustCrack {
+    /// The LCG most values agree with, with the state set to what it produced at the last position even if that value was wrong
+    pub lcg: LCG,
+    /// Positions whose value the LCG reproduces
+    pub inliers: Vec<usize>,
+    /// Positions whose value doesn't fit
+    pub outliers: Vec<usize>,
+}
+
+/// What `lcg` produces at every position when it outputs `values[anchor]` at `anchor`
+///
+/// Positions before the anchor are None when a isn't invertible
+fn predict(lcg: &LCG, values: &[BigInt], anchor: usize) -> Vec<Option<BigInt>> {
+    let mut predicted = vec![None; values.len()];
+    predicted[anchor] = Some(values[anchor].clone());
+    let mut forward = LCG {
+        state: values[anchor].clone(),
+        ..lcg.clone()
+    };
+    for slot in &mut predicted[anchor + 1..] {
+        *slot = Some(forward.rand());
+    }
+    let mut backward = LCG {
+        state: values[anchor].clone(),
+        ..lcg.clone()
+    };
+    for slot in predicted[..anchor].iter_mut().rev() {
+        *slot = backward.prev();
+    }
+    predicted
+}
+
+/// Crack an LCG from consecutive values where a few are wrong, RANSAC style
+///
+/// Every run of `window` consecutive values is cracked on its own, and each candidate predicts the whole sequence from that window.
+/// The candidate reproducing the most values wins, ties going to the smaller modulus, and it has to reproduce more than half of them.
+/// A single bad value ruins every window it's in, so `window` trades how many values the modulus is inferred from against how many clean windows are left, 6 to 8 works for most moduli.
+///
+/// Off-by-one captures show up as outliers as long as the rest of the sequence stays in step
+pub fn crack_lcg_robust<T: Clone + Into<BigInt>>(
+    values: &[T],
+    window: usize,
+) -> Option<RobustCrack> {
+    let values = to_bigints(values);
+    if window == 0 || window > values.len() {
+        return None;
+    }
+    let mut best: Option<(usize, LCG, Vec<Option<BigInt>>)> = None;
+    for anchor in 0..=values.len() - window {
+        for lcg in crack_lcg_all(&values[anchor..anchor + window]) {
+            let predicted = predict(&lcg, &values, anchor);
+            let score = predicted
+                .iter()
+                .zip(&values)
+                .filter(|(x, y)| x.as_ref() == Some(y))
+                .count();
+            let better = match &best {
+                Some((best_score, best_lcg, _)) => {
+                    score > *best_score || (score == *best_score && lcg.m < best_lcg.m)
+                }
+                None => true,
+            };
+            if better {
+                best = Some((score, lcg, predicted));
+            }
+        }
+    }
+
+    let (score, lcg, predicted) = best?;
+    if score * 2 <= values.len() {
+        return None;
+    }
+    let (inliers, outliers) =
+        (0..values.len()).partition(|&i| predicted[i].as_ref() == Some(&values[i]));
+    Some(RobustCrack {
+        lcg: LCG {
+            state: predicted.last()?.clone()?,
+            ..lcg
+        },
+        inliers,
+        outliers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{crack_lcg, crack_lcg_robust, LCG};
+
+    #[test]
+    fn it_cracks_through_corrupted_values() {
+        let mut rand = LCG {
+            state: 123456789u64,
+            a: 48271,
+            c: 11,
+            m: 2147483647,
+        };
+        let mut values = (&mut rand).take(24).collect::<Vec<_>>();
+        // a transcription error and an off-by-one capture
+        values[3] = 987654321;
+        values[14] = values[15];
+        assert_ne!(crack_lcg(&values), rand.convert());
+
+        let robust = crack_lcg_robust(&values, 6).unwrap();
+        assert_eq!(robust.lcg, rand.convert().unwrap());
+        assert_eq!(robust.outliers, vec![3, 14]);
+        assert_eq!(robust.inliers.len(), 22);
+
+        // a corrupted last value doesn't leak into the state
+        let last = values.len() - 1;
+        values[last] += 1;
+        let robust = crack_lcg_robust(&values, 6).unwrap();
+        assert_eq!(robust.lcg, rand.convert().unwrap());
+        assert_eq!(robust.outliers, vec![3, 14, last]);
+    }
+}
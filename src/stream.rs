@@ -0,0 +1,165 @@
+//! Cracking one value at a time, see [`LcgCracker`]
+
+use crate::crack::REFINE_BOUND;
+use crate::{crack_lcg_with, math, KnownParams, LCG};
+use num::{Integer, Signed, ToPrimitive, Zero};
+use num_bigint::BigInt;
+
+/// What an [`LcgCracker`] knows so far
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CrackStatus {
+    /// m isn't pinned down yet, along with the multiple of it the values give so far if there is one
+    Undetermined {
+        /// Multiple of m, None until there are 4 values
+        modulus_multiple: Option<BigInt>,
+    },
+    /// m is certain but several (a, c) fit, which more values may or may not resolve
+    Ambiguous {
+        /// Every LCG that fits, with the state set to the last value
+        candidates: Vec<LCG>,
+    },
+    /// m, a and c are certain, with the state set to the last value
+    Determined(LCG),
+    /// No LCG produces these values
+    Inconsistent,
+}
+
+/// Incremental version of [`crack_lcg_with`] for values that arrive one at a time, such as from a live service
+///
+/// [`push`](LcgCracker::push) keeps the running gcds that [`crack_lcg`](crate::crack_lcg) would recompute from scratch, so it costs O(1) BigInt operations.
+/// [`status`](LcgCracker::status) says when to stop querying
+#[derive(Debug, Clone, Default)]
+pub struct LcgCracker {
+    values: Vec<BigInt>,
+    max: BigInt,
+    /// gcd of `d2*d0 - d1^2` over consecutive differences
+    zeroes: BigInt,
+    /// gcd of the differences themselves, which the zeroes overshoot m by
+    differences: BigInt,
+}
+
+impl LcgCracker {
+    /// Start with no values
+    pub fn new() -> LcgCracker {
+        LcgCracker::default()
+    }
+
+    /// Number of values pushed so far
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Whether nothing has been pushed yet
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Add the next consecutive output
+    pub fn push(&mut self, value: impl Into<BigInt>) {
+        let value = value.into();
+        let n = self.values.len();
+        if n >= 1 {
+            self.differences = self.differences.gcd(&(&value - &self.values[n - 1]));
+        }
+        if n >= 3 {
+            let d0 = &self.values[n - 2] - &self.values[n - 3];
+            let d1 = &self.values[n - 1] - &self.values[n - 2];
+            let d2 = &value - &self.values[n - 1];
+            self.zeroes = self.zeroes.gcd(&(d2 * d0 - &d1 * &d1));
+        }
+        if value > self.max {
+            self.max = value.clone();
+        }
+        self.values.push(value);
+    }
+
+    /// The multiple of m the values pin down so far
+    fn modulus_multiple(&self) -> Option<BigInt> {
+        if self.zeroes.is_zero() {
+            None
+        } else {
+            Some(&self.zeroes / &self.differences)
+        }
+    }
+
+    /// Whether m has to be `multiple` itself
+    ///
+    /// m divides the multiple and is bigger than every value, so once the multiple is below max * [`REFINE_BOUND`] any spurious factor is a small prime and those can be checked directly
+    fn is_certain(&self, multiple: &BigInt) -> bool {
+        if !self.max.is_positive() {
+            return false;
+        }
+        let ratio = multiple / &self.max;
+        if ratio >= BigInt::from(REFINE_BOUND) {
+            return false;
+        }
+        math::small_primes(ratio.to_usize().unwrap_or(0) + 1)
+            .into_iter()
+            .all(|p| !(multiple % p).is_zero() || multiple / p <= self.max)
+    }
+
+    /// Whether m, a and c are pinned down yet
+    ///
+    /// This is only certain rather than likely, so it can take a value or two more than [`crack_lcg`](crate::crack_lcg) needs to guess right
+    pub fn status(&self) -> CrackStatus {
+        let multiple = match self.modulus_multiple() {
+            Some(multiple) => multiple,
+            None => {
+                return CrackStatus::Undetermined {
+                    modulus_multiple: None,
+                }
+            }
+        };
+        if multiple <= self.max {
+            return CrackStatus::Inconsistent;
+        }
+        if !self.is_certain(&multiple) {
+            return CrackStatus::Undetermined {
+                modulus_multiple: Some(multiple),
+            };
+        }
+        let known = KnownParams {
+            m: Some(multiple),
+            ..KnownParams::default()
+        };
+        let mut candidates = crack_lcg_with(&known, &self.values);
+        match candidates.len() {
+            0 => CrackStatus::Inconsistent,
+            1 => CrackStatus::Determined(candidates.remove(0)),
+            _ => CrackStatus::Ambiguous { candidates },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CrackStatus, LcgCracker, LCG};
+
+    #[test]
+    fn it_stops_once_everything_is_pinned_down() {
+        let mut rand = LCG {
+            state: 22u64,
+            a: 48271,
+            c: 12345,
+            m: 2147483647,
+        };
+        let mut cracker = LcgCracker::new();
+        let mut pushed = 0;
+        let cracked = loop {
+            cracker.push(rand.rand());
+            pushed += 1;
+            match cracker.status() {
+                CrackStatus::Determined(lcg) => break lcg,
+                CrackStatus::Undetermined { .. } => assert!(pushed < 20),
+                status => panic!("unexpected {:?}", status),
+            }
+        };
+        assert_eq!(cracked, rand.convert().unwrap());
+        assert_eq!(cracker.len(), pushed);
+        assert!(pushed >= 4);
+
+        cracker.push(0);
+        cracker.push(1);
+        assert_eq!(cracker.status(), CrackStatus::Inconsistent);
+    }
+}
@@ -0,0 +1,147 @@
+//! Cracking when only every k-th output is seen, see [`crack_lcg_strided`]
+
+use crate::crack::MAX_CANDIDATES;
+use crate::{affine_pow, crack_lcg_all, math, LCG};
+use num::{One, ToPrimitive, Zero};
+use num_bigint::BigInt;
+
+/// A single step LCG which produces the observed values when stepped `stride` times between each of them
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Strided {
+    /// How many outputs were consumed per observed value
+    pub stride: u64,
+    /// The underlying LCG, with the state set to the last value
+    pub lcg: LCG,
+}
+
+impl LCG {
+    /// Every LCG which takes `k` steps to do what this one does in one, sorted by (a, c)
+    ///
+    /// Stepping k times is the affine map `(a^k, c*(1 + a + ... + a^(k-1)))` so a is a k-th root of this multiplier mod m, found per prime power of m and combined with the CRT,
+    /// and c then solves a linear congruence. Large prime factors of m with gcd(k, p-1) != 1 take a discrete log, but only over the primes shared with k so it stays fast for small k.
+    /// Returns nothing if there are more than 2^16 multipliers
+    pub fn step_roots(&self, k: u64) -> Vec<LCG> {
+        if k == 0 {
+            return vec![];
+        }
+        let mut multipliers = vec![BigInt::zero()];
+        let mut combined = BigInt::one();
+        for (p, e) in math::factor(&self.m) {
+            let roots = match math::kth_roots(k, &self.a, &p, e, MAX_CANDIDATES) {
+                Some(roots) => roots,
+                None => return vec![],
+            };
+            let pe = p.pow(e);
+            multipliers = multipliers
+                .iter()
+                .flat_map(|x| {
+                    roots
+                        .iter()
+                        .filter_map(|r| math::crt(x, &combined, r, &pe))
+                        .collect::<Vec<_>>()
+                })
+                .map(|(x, _)| x)
+                .collect();
+            if multipliers.len() > MAX_CANDIDATES {
+                return vec![];
+            }
+            combined *= pe;
+        }
+        multipliers.sort();
+
+        let mut lcgs = vec![];
+        for a in multipliers {
+            let (_, sum) = affine_pow(&a, &BigInt::one(), &BigInt::from(k), &self.m);
+            let (first, step) = match math::solve_linear(&sum, &self.c, &self.m) {
+                Some(solution) => solution,
+                None => continue,
+            };
+            let count = match (&self.m / &step).to_usize() {
+                Some(count) if lcgs.len() + count <= MAX_CANDIDATES => count,
+                _ => return vec![],
+            };
+            lcgs.extend((0..count).map(|i| LCG {
+                state: self.state.clone(),
+                a: a.clone(),
+                c: &first + &step * i,
+                m: self.m.clone(),
+            }));
+        }
+        lcgs
+    }
+}
+
+/// Crack an LCG from every k-th output when k isn't known, for example when a program consumes k random numbers per visible event
+///
+/// The observed values are themselves an LCG which [`crack_lcg_all`] finds, and every stride up to `max_stride` is undone with [`LCG::step_roots`].
+/// Stride 1 always fits and so do multiples of the real stride, smaller strides are the simpler explanation
+pub fn crack_lcg_strided<T: Clone + Into<BigInt>>(values: &[T], max_stride: u64) -> Vec<Strided> {
+    crack_lcg_all(values)
+        .iter()
+        .flat_map(|observed| {
+            (1..=max_stride).flat_map(move |stride| {
+                observed
+                    .step_roots(stride)
+                    .into_iter()
+                    .map(move |lcg| Strided { stride, lcg })
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{crack_lcg_strided, Strided, LCG};
+    use num_bigint::BigInt;
+
+    #[test]
+    fn it_undoes_an_unknown_stride() {
+        let mut rand = LCG {
+            state: 123456789u64,
+            a: 48271,
+            c: 11,
+            m: 2147483647,
+        };
+        let values = (0..10)
+            .map(|_| LCG::skip(&mut rand, &BigInt::from(3)))
+            .collect::<Vec<_>>();
+        let cracked = crack_lcg_strided(&values, 4);
+        assert!(cracked.contains(&Strided {
+            stride: 3,
+            lcg: rand.convert().unwrap(),
+        }));
+        assert!(cracked.iter().any(|x| x.stride == 1));
+        for Strided { stride, lcg } in cracked {
+            let mut replay = LCG {
+                state: BigInt::from(values[0]),
+                ..lcg
+            };
+            for value in &values[1..] {
+                assert_eq!(
+                    LCG::skip(&mut replay, &BigInt::from(stride)),
+                    BigInt::from(*value)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn it_finds_every_root_mod_a_power_of_two() {
+        let rand = LCG {
+            state: 0u64.into(),
+            a: 0x5DEECE66Du64.into(),
+            c: 11.into(),
+            m: BigInt::from(1) << 48,
+        };
+        let mut squared = rand.clone();
+        squared.a = &rand.a * &rand.a % &rand.m;
+        squared.c = &rand.c * (&rand.a + 1) % &rand.m;
+        let roots = squared.step_roots(2);
+        assert!(roots.contains(&rand));
+        // x^2 ≡ a^2 has 4 odd roots mod 2^48, only a and a + 2^47 reach c*(a+1) and both with 2 increments since a + 1 is even
+        assert_eq!(roots.len(), 4);
+        assert!(roots
+            .iter()
+            .all(|x| x.a == rand.a || x.a == &rand.a + (BigInt::from(1) << 47)));
+    }
+}
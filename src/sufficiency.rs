@@ -0,0 +1,159 @@
+//! How many values cracking needs, see [`values_needed`]
+//!
+//! Every extra value adds another random multiple of m to the gcd in [`crack_lcg`](crate::crack_lcg), and after dividing out the gcd of the differences
+//! and stripping spurious factors below 4096 the modulus is only wrong if a bigger prime divides every one of them. Everything in here is built on how likely that is
+
+use crate::crack::{
+    candidate_moduli, unstripped_factor_probability, values_per_zero, REFINE_BOUND,
+};
+use crate::KnownParams;
+use num::{Signed, ToPrimitive, Zero};
+use num_bigint::BigInt;
+
+/// Give up rather than ask for more values than this
+const MAX_VALUES: usize = 1 << 10;
+
+/// Probability that cracking `values` consecutive outputs of an LCG whose modulus has `modulus_bits` bits infers exactly m
+///
+/// That needs at least one multiple of m to take the gcd of, and then fails only when a prime too big to strip divides all of them.
+/// Moduli of 12 bits or fewer can't hide such a prime. With m known this is 1 as soon as there are enough values to solve for the rest, see [`crack_lcg_with`](crate::crack_lcg_with)
+pub fn modulus_probability(modulus_bits: u64, values: usize, known: &KnownParams) -> f64 {
+    if known.m.is_some() {
+        return 1.0;
+    }
+    let zeroes = values.saturating_sub(values_per_zero(known));
+    if zeroes == 0 {
+        0.0
+    } else if modulus_bits <= u64::from(REFINE_BOUND.trailing_zeros()) {
+        1.0
+    } else {
+        1.0 - unstripped_factor_probability(zeroes)
+    }
+}
+
+/// Smallest number of consecutive outputs which infers the modulus exactly with at least `confidence`, such as 0.999
+///
+/// Returns None for a confidence outside (0, 1) or one that'd take more than 1024 values.
+/// This only covers m, the multiplier and increment can still be ambiguous when the values share factors with m
+pub fn values_needed(modulus_bits: u64, confidence: f64, known: &KnownParams) -> Option<usize> {
+    if !(confidence > 0.0 && confidence < 1.0) {
+        return None;
+    }
+    if known.m.is_some() {
+        // solving for whatever is left takes one value fewer than the first expression would
+        return Some(values_per_zero(known));
+    }
+    (1..=MAX_VALUES)
+        .map(|zeroes| zeroes + values_per_zero(known))
+        .find(|&values| modulus_probability(modulus_bits, values, known) >= confidence)
+}
+
+/// After cracking, the probability that `modulus` is still a multiple of the real one
+///
+/// The real modulus is `modulus/q` for some q, and has to be bigger than every value which rules out all but a few small q.
+/// Each remaining q below 4096 is weighed by how likely the gcd is to pick up exactly that factor, `q^-zeroes`, with `zeroes` counting the values after the first 3, or fewer when a, c or the seed is known
+pub fn spurious_modulus_probability<T: Clone + Into<BigInt>>(
+    modulus: &BigInt,
+    known: &KnownParams,
+    values: &[T],
+) -> f64 {
+    let max: BigInt = values
+        .iter()
+        .cloned()
+        .map(Into::into)
+        .max()
+        .unwrap_or_else(BigInt::zero);
+    if !modulus.is_positive() || known.m.is_some() {
+        return 0.0;
+    }
+    let zeroes = values.len().saturating_sub(values_per_zero(known));
+    if zeroes == 0 {
+        return 1.0;
+    }
+    // only q with modulus/q > max are possible, and only small ones get stripped
+    let divisors = candidate_moduli(modulus, &max.max(BigInt::zero()))
+        .into_iter()
+        .map(|m| modulus / m)
+        .collect::<Vec<_>>();
+    let weights = divisors
+        .iter()
+        .map(|q| q.to_f64().unwrap_or(f64::INFINITY).powi(-(zeroes as i32)))
+        .collect::<Vec<_>>();
+    let total = weights.iter().sum::<f64>();
+    (total - 1.0) / total
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        crack_lcg, modulus_probability, spurious_modulus_probability, values_needed, KnownParams,
+        LCG,
+    };
+    use num_bigint::BigInt;
+
+    #[test]
+    fn it_estimates_how_many_values_are_needed() {
+        let unknown = KnownParams::default();
+        let needed = values_needed(64, 0.999, &unknown).unwrap();
+        assert!(modulus_probability(64, needed, &unknown) >= 0.999);
+        assert!(modulus_probability(64, needed - 1, &unknown) < 0.999);
+        // 2 multiples of m share a prime above 4096 with probability about 3e-5
+        assert_eq!(needed, 5);
+        for seed in 1..20u64 {
+            let rand = LCG {
+                state: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) % 18446744073709551557,
+                a: 6364136223846793005,
+                c: 1442695040888963407,
+                m: 18446744073709551557u64,
+            };
+            let values = rand.clone().take(needed).collect::<Vec<_>>();
+            assert_eq!(crack_lcg(&values).unwrap().m, BigInt::from(rand.m));
+        }
+        assert!(
+            values_needed(
+                64,
+                0.999,
+                &KnownParams {
+                    a: Some(3.into()),
+                    ..KnownParams::default()
+                }
+            ) < Some(needed)
+        );
+        assert_eq!(
+            values_needed(
+                64,
+                0.999,
+                &KnownParams {
+                    m: Some(7.into()),
+                    ..KnownParams::default()
+                }
+            ),
+            Some(3)
+        );
+        assert_eq!(values_needed(64, 1.0, &unknown), None);
+    }
+
+    #[test]
+    fn it_estimates_whether_a_modulus_is_spurious() {
+        let mut rand = LCG {
+            state: 22u64,
+            a: 48271,
+            c: 0,
+            m: 2147483647,
+        };
+        let values = (&mut rand).take(12).collect::<Vec<_>>();
+        let cracked = crack_lcg(&values).unwrap();
+        let unknown = KnownParams::default();
+        assert!(spurious_modulus_probability(&cracked.m, &unknown, &values) < 0.01);
+        // twice m would still be bigger than every value and 2 divides it
+        let doubled = &cracked.m * 2;
+        let few = spurious_modulus_probability(&doubled, &unknown, &values[..5]);
+        let many = spurious_modulus_probability(&doubled, &unknown, &values);
+        assert!(few > many);
+        assert!(many > 0.0);
+        assert_eq!(
+            spurious_modulus_probability(&BigInt::from(2147483647), &unknown, &[1, 2, 3]),
+            1.0
+        );
+    }
+}
@@ -0,0 +1,404 @@
+//! Cracking when only some bits of each state leak, see [`recover_state_window`] for known parameters and [`crack_lcg_truncated`] for secret ones
+
+use crate::crack::{to_bigints, MAX_CANDIDATES};
+use crate::poly::{eval_mod, gcd_mod, trim};
+use crate::{lll, modulo, solve_bounded, LCG};
+use num::{Integer, One, Signed, Zero};
+use num_bigint::BigInt;
+
+/// Bits [lo, hi) of `state`
+pub(crate) fn window(state: &BigInt, lo: u32, hi: u32) -> BigInt {
+    (state >> lo as usize) & ((BigInt::one() << (hi - lo) as usize) - 1u32)
+}
+
+/// Whether `x0` followed by `a`, `c` and `m` leaks exactly `outputs` through bits [lo, hi)
+fn reproduces(lcg: &LCG, x0: &BigInt, lo: u32, hi: u32, outputs: &[BigInt]) -> bool {
+    lcg.states_from(x0)
+        .zip(outputs)
+        .all(|(state, y)| &window(&state, lo, hi) == y)
+}
+
+/// Congruences `A_i * x_0 + C_i - x_i ≡ 0 (mod m)` tying every state to the first, for [`solve_bounded`]
+///
+/// Each state is `known[i] + units[0] * z_(i,0) + units[1] * z_(i,1) + ...` with the unknowns laid out state by state
+pub(crate) fn state_congruences(
+    lcg: &LCG,
+    known: &[BigInt],
+    units: &[BigInt],
+) -> (Vec<Vec<BigInt>>, Vec<BigInt>) {
+    let m = &lcg.m;
+    let (mut big_a, mut big_c) = (BigInt::one(), BigInt::zero());
+    let mut rows = vec![];
+    let mut targets = vec![];
+    for i in 1..known.len() {
+        big_a = modulo(&(&big_a * &lcg.a), m);
+        big_c = modulo(&(&big_c * &lcg.a + &lcg.c), m);
+        let mut row = vec![BigInt::zero(); units.len() * known.len()];
+        for (j, unit) in units.iter().enumerate() {
+            row[j] = &big_a * unit;
+            row[units.len() * i + j] = -unit;
+        }
+        rows.push(row);
+        targets.push(&known[i] - &big_c - &big_a * &known[0]);
+    }
+    (rows, targets)
+}
+
+/// Run `solve` on the first few outputs, falling back to more when what it finds doesn't reproduce them all
+///
+/// Lattices grow with every output so this starts from a couple more than the leaked bits need
+fn from_prefixes(
+    lcg: &LCG,
+    lo: u32,
+    hi: u32,
+    outputs: &[BigInt],
+    solve: impl Fn(&[BigInt]) -> Option<LCG>,
+) -> Option<LCG> {
+    let leaked = u64::from(hi - lo).max(1);
+    let mut count = (2 * lcg.m.bits()).div_ceil(leaked) as usize + 2;
+    loop {
+        count = count.min(outputs.len());
+        let found = solve(&outputs[..count]);
+        if let Some(lcg) = found.filter(|lcg| reproduces(lcg, &lcg.state, lo, hi, outputs)) {
+            return Some(lcg);
+        }
+        if count == outputs.len() {
+            return None;
+        }
+        count *= 2;
+    }
+}
+
+/// Full state behind the first output of a known LCG, from the leaked bits [lo, hi) of consecutive states
+///
+/// Every state is `high * 2^hi + output * 2^lo + low` and `x_i = A_i * x_0 + C_i mod m` ties them together, so the unknown high and low parts
+/// are a small solution of linear congruences, see [`solve_bounded`]
+fn known_state(lcg: &LCG, lo: u32, hi: u32, outputs: &[BigInt]) -> Option<LCG> {
+    let m = &lcg.m;
+    let high_bound = (m + (BigInt::one() << hi as usize) - 1u32) >> hi as usize;
+    let has_high = high_bound > BigInt::one();
+    let has_low = lo > 0;
+    let known = outputs.iter().map(|y| y << lo as usize).collect::<Vec<_>>();
+    let mut units = vec![];
+    let mut bounds = vec![];
+    if has_high {
+        units.push(BigInt::one() << hi as usize);
+        bounds.push(high_bound);
+    }
+    if has_low {
+        units.push(BigInt::one());
+        bounds.push(BigInt::one() << lo as usize);
+    }
+    let bounds = outputs
+        .iter()
+        .flat_map(|_| bounds.iter().cloned())
+        .collect::<Vec<_>>();
+
+    let z = if bounds.is_empty() {
+        vec![]
+    } else {
+        let (rows, targets) = state_congruences(lcg, &known, &units);
+        solve_bounded(&rows, &targets, m, &bounds)?
+    };
+    let state = &known[0]
+        + units
+            .iter()
+            .zip(&z)
+            .map(|(unit, zi)| unit * zi)
+            .sum::<BigInt>();
+    Some(LCG {
+        state,
+        ..lcg.clone()
+    })
+}
+
+/// Increment and full state behind the first output for known a and m, from consecutive `state >> shift` outputs
+///
+/// c cancels out of the differences `d_i = x_(i+1) - x_i ≡ a^i * d_0`, and each is `2^shift * (y_(i+1) - y_i)` plus something in (-2^shift, 2^shift), which [`solve_bounded`] finds.
+/// Moving every state by the same amount keeps the differences, so the low bits of x_0 are anything that keeps every state's low bits in range, the smallest is used
+fn increment_and_state(lcg: &LCG, shift: u32, outputs: &[BigInt]) -> Option<LCG> {
+    let m = &lcg.m;
+    let unit = BigInt::one() << shift as usize;
+    let offset = &unit - 1u32;
+    let differences = outputs
+        .windows(2)
+        .map(|pair| (&pair[1] - &pair[0]) << shift as usize)
+        .collect::<Vec<_>>();
+    if differences.is_empty() {
+        return None;
+    }
+    // z_i = error_i + offset so every unknown is in [0, 2 * 2^shift - 1)
+    let bounds = vec![&unit * 2u32 - 1u32; differences.len()];
+    let mut rows = vec![];
+    let mut targets = vec![];
+    for i in 1..differences.len() {
+        let mut row = vec![BigInt::zero(); differences.len()];
+        row[i] = BigInt::one();
+        row[i - 1] = -&lcg.a;
+        rows.push(row);
+        targets.push(&lcg.a * &differences[i - 1] - &differences[i] + &offset - &lcg.a * &offset);
+    }
+    let z = solve_bounded(&rows, &targets, m, &bounds)?;
+
+    // running sums of the errors are how far each state's low bits sit from x_0's
+    let mut drift = vec![BigInt::zero()];
+    for zi in &z {
+        let next = drift.last()? + zi - &offset;
+        drift.push(next);
+    }
+    let low = -drift.iter().min()?;
+    if &low + drift.iter().max()? >= unit {
+        return None;
+    }
+    let x0 = (&outputs[0] << shift as usize) + &low;
+    let x1 = &x0 + &differences[0] + &z[0] - &offset;
+    Some(LCG {
+        c: (x1 - &lcg.a * &x0).mod_floor(m),
+        state: x0,
+        ..lcg.clone()
+    })
+}
+
+/// Recover the full state of a known LCG from bits [lo, hi) of consecutive states, `outputs[i] = (x_i >> lo) % 2^(hi - lo)`
+///
+/// The returned LCG has its state set to the full state behind the first output so [`rand`](LCG::rand) carries on with the second.
+/// Only `a`, `c` and `m` of `lcg` are used. It takes enough outputs for the leaked bits to outweigh the hidden ones, as a rule of thumb
+/// `outputs.len() * (hi - lo)` should be comfortably above the bit length of m. Returns None when the reduction doesn't find a state reproducing every output
+pub fn recover_state_window<T: Clone + Into<BigInt>>(
+    lcg: &LCG,
+    lo: u32,
+    hi: u32,
+    outputs: &[T],
+) -> Option<LCG> {
+    let outputs = to_bigints(outputs);
+    let limit = BigInt::one() << hi.checked_sub(lo)? as usize;
+    if lo >= hi
+        || outputs.is_empty()
+        || !lcg.m.is_positive()
+        || outputs.iter().any(|y| y.is_negative() || y >= &limit)
+    {
+        return None;
+    }
+    from_prefixes(lcg, lo, hi, &outputs, |prefix| {
+        known_state(lcg, lo, hi, prefix)
+    })
+}
+
+/// Recover the full state of a known LCG from consecutive `state >> shift` outputs, the top bits of the state
+///
+/// Shorthand for [`recover_state_window`] with the window running up to the top of m
+pub fn recover_state_truncated<T: Clone + Into<BigInt>>(
+    lcg: &LCG,
+    shift: u32,
+    outputs: &[T],
+) -> Option<LCG> {
+    let hi = (lcg.m.bits() as u32).max(shift + 1);
+    recover_state_window(lcg, shift, hi, outputs)
+}
+
+/// Polynomials with a as a root mod m, from consecutive `state >> shift` outputs
+///
+/// The differences `d_i = x_(i+1) - x_i` satisfy `d_i ≡ a^i * d_0`, so a small λ with `sum(λ_i * d_(i+j)) ≡ 0 mod m` for every j makes
+/// `sum(λ_i * a^i)` vanish at a. The differences are known up to 2^shift, which is plenty to find such λ as the shortest rows of a lattice, Stern's attack
+fn relations(m: &BigInt, shift: u32, outputs: &[BigInt]) -> Vec<Vec<BigInt>> {
+    let differences = outputs
+        .windows(2)
+        .map(|pair| (&pair[1] - &pair[0]) << shift as usize)
+        .collect::<Vec<_>>();
+    let columns = (differences.len() / 3).max(2);
+    if differences.len() < columns + 2 {
+        return vec![];
+    }
+    let rows = differences.len() + 1 - columns;
+    let unit = BigInt::one() << shift as usize;
+    let mut basis = vec![];
+    for i in 0..rows {
+        let mut row = vec![BigInt::zero(); rows + columns];
+        row[i] = unit.clone();
+        row[rows..].clone_from_slice(&differences[i..i + columns]);
+        basis.push(row);
+    }
+    for j in 0..columns {
+        let mut row = vec![BigInt::zero(); rows + columns];
+        row[rows + j] = m.clone();
+        basis.push(row);
+    }
+    let reduced = match lll(&basis) {
+        Ok(reduced) => reduced,
+        Err(_) => return vec![],
+    };
+    reduced
+        .into_iter()
+        .map(|row| trim(row[..rows].iter().map(|x| x / &unit).collect()))
+        .filter(|p| p.len() > 1)
+        .collect()
+}
+
+/// Candidates for a shared by as many of `polynomials` as possible, shortest first
+///
+/// The longer rows of the reduced lattice aren't always real relations, so any polynomial which would leave no root is skipped.
+/// A power of two m isn't a field so roots are lifted one bit at a time there, otherwise the gcd should come down to `x - a`
+fn multipliers(polynomials: &[Vec<BigInt>], m: &BigInt) -> Vec<BigInt> {
+    let bits = m.trailing_zeros().unwrap_or(0);
+    if m.bits() == bits + 1 {
+        let mut roots = vec![BigInt::zero()];
+        for bit in 0..bits {
+            let modulus = BigInt::one() << (bit + 1) as usize;
+            let lifted: Vec<BigInt> = roots
+                .iter()
+                .flat_map(|r| vec![r.clone(), r + (BigInt::one() << bit as usize)])
+                .collect();
+            let mut kept = lifted;
+            for p in polynomials {
+                let fewer = kept
+                    .iter()
+                    .filter(|r| eval_mod(p, r, &modulus).is_zero())
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if !fewer.is_empty() {
+                    kept = fewer;
+                }
+            }
+            if kept.len() > MAX_CANDIDATES {
+                return vec![];
+            }
+            roots = kept;
+        }
+        return roots;
+    }
+    let mut common: Option<Vec<BigInt>> = None;
+    for p in polynomials {
+        let next = match &common {
+            Some(q) => gcd_mod(q, p, m),
+            None => gcd_mod(p, p, m),
+        };
+        if let Some(next) = next.filter(|g| g.len() > 1) {
+            common = Some(next);
+        }
+    }
+    match common {
+        Some(g) if g.len() == 2 => vec![(m - &g[0]) % m],
+        _ => vec![],
+    }
+}
+
+/// Crack an LCG with known m but unknown a and c from consecutive `state >> shift` outputs
+///
+/// Works for prime m and for powers of two. It takes more outputs the fewer bits leak, around 16 when half of a 61 or 64 bit state leaks,
+/// and `outputs.len() * (bits of m - shift)` should be a few times the bits of m.
+///
+/// Every returned LCG reproduces every output, with the state set to the full state behind the first output like [`recover_state_truncated`].
+/// Moving every state by the same small amount, and c to match, leaves the outputs alone as long as nothing carries into the top bits,
+/// so the low bits of the state and c are only pinned down up to that shift. An empty result means the attack didn't find anything, not that there is no such LCG
+pub fn crack_lcg_truncated<T: Clone + Into<BigInt>>(
+    m: &BigInt,
+    shift: u32,
+    outputs: &[T],
+) -> Vec<LCG> {
+    let outputs = to_bigints(outputs);
+    let hi = (m.bits() as u32).max(shift + 1);
+    if !m.is_positive()
+        || outputs
+            .iter()
+            .any(|y| y.is_negative() || (y << shift as usize) >= *m)
+    {
+        return vec![];
+    }
+    let mut lcgs: Vec<LCG> = vec![];
+    for a in multipliers(&relations(m, shift, &outputs), m) {
+        let guess = LCG {
+            state: BigInt::zero(),
+            a,
+            c: BigInt::zero(),
+            m: m.clone(),
+        };
+        let found = from_prefixes(&guess, shift, hi, &outputs, |prefix| {
+            increment_and_state(&guess, shift, prefix)
+        });
+        if let Some(lcg) = found {
+            if !lcgs.contains(&lcg) {
+                lcgs.push(lcg);
+            }
+        }
+    }
+    lcgs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::window;
+    use crate::{crack_lcg_truncated, recover_state_truncated, recover_state_window, LCG};
+    use num_bigint::BigInt;
+
+    fn lcg() -> LCG {
+        LCG {
+            state: BigInt::from(1234567890123456789u64),
+            a: BigInt::from(0x5DEECE66Du64),
+            c: 11.into(),
+            m: (BigInt::from(1) << 61) - 1,
+        }
+    }
+
+    #[test]
+    fn it_recovers_the_state_from_top_bits() {
+        let mut rand = lcg();
+        let states = (&mut rand).take(8).collect::<Vec<_>>();
+        let outputs = states.iter().map(|x| x >> 40).collect::<Vec<_>>();
+        let public = LCG {
+            state: 0.into(),
+            ..lcg()
+        };
+        let mut recovered = recover_state_truncated(&public, 40, &outputs).unwrap();
+        assert_eq!(recovered.state, states[0]);
+        assert_eq!(LCG::skip(&mut recovered, &7.into()), states[7]);
+    }
+
+    #[test]
+    fn it_recovers_the_state_from_a_bit_window() {
+        let mut rand = lcg();
+        let states = (&mut rand).take(10).collect::<Vec<_>>();
+        let outputs = states.iter().map(|x| window(x, 20, 44)).collect::<Vec<_>>();
+        let recovered = recover_state_window(&lcg(), 20, 44, &outputs).unwrap();
+        assert_eq!(recovered.state, states[0]);
+        assert_eq!(recover_state_window(&lcg(), 44, 20, &outputs), None);
+
+        // negative parameters step the same as their positive residues
+        let negative = LCG {
+            a: &rand.a - &rand.m,
+            c: &rand.c - &rand.m,
+            ..lcg()
+        };
+        let recovered = recover_state_window(&negative, 20, 44, &outputs).unwrap();
+        assert_eq!(recovered.state, states[0]);
+    }
+
+    #[test]
+    fn it_cracks_secret_parameters_from_top_bits() {
+        // moving every state by the same w keeps the top bits as long as it doesn't carry, and c moves by w * (a - 1) to match
+        let shifted = |cracked: &LCG, real: &LCG, x0: &BigInt| {
+            (&cracked.c - &real.c - (x0 - &cracked.state) * (&real.a - 1)) % &real.m
+                == BigInt::from(0)
+        };
+
+        let mut rand = lcg();
+        let states = (&mut rand).take(16).collect::<Vec<_>>();
+        let outputs = states.iter().map(|x| x >> 30).collect::<Vec<_>>();
+        let cracked = crack_lcg_truncated(&rand.m, 30, &outputs);
+        assert_eq!(cracked.len(), 1);
+        assert_eq!(cracked[0].a, rand.a);
+        assert!(shifted(&cracked[0], &rand, &states[0]));
+
+        let mut rand = LCG {
+            state: BigInt::from(88172645463325252u64),
+            a: BigInt::from(6364136223846793005u64),
+            c: 1442695040888963407u64.into(),
+            m: BigInt::from(1) << 64,
+        };
+        let states = (&mut rand).take(24).collect::<Vec<_>>();
+        let outputs = states.iter().map(|x| x >> 32).collect::<Vec<_>>();
+        let cracked = crack_lcg_truncated(&rand.m, 32, &outputs);
+        assert!(cracked
+            .iter()
+            .any(|lcg| lcg.a == rand.a && shifted(lcg, &rand, &states[0])));
+    }
+}